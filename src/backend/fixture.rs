@@ -0,0 +1,211 @@
+//! An in-memory backend serving recorded data
+//!
+//! Lets the entire checker run offline, useful for testing and development
+
+use std::{collections::HashMap, fs, path::Path, str::FromStr, sync::RwLock};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::{
+    backend::LeaderboardBackend,
+    miu::{
+        score::{Replay, Score},
+        weekly_data::{Challenge, Weekly},
+    },
+    request::error::ParseError,
+};
+
+/// Serves world records, weekly challenges and replays from memory
+///
+/// Can be filled in manually with the `set_*` functions,
+/// or loaded from a directory of recorded data with `FixtureBackend::load`
+#[derive(Debug, Default)]
+pub struct FixtureBackend {
+    /// World records, keyed by the raw mapid (`SP_###`)
+    wrs: RwLock<HashMap<String, Score>>,
+    /// Full leaderboards, keyed by the raw mapid (`SP_###`)
+    leaderboards: RwLock<HashMap<String, Vec<Score>>>,
+    /// The weekly challenge stats
+    weekly: RwLock<Option<Weekly>>,
+    /// Weekly scores, keyed by challenge id
+    weekly_scores: RwLock<HashMap<String, Vec<Score>>>,
+    /// Replay bytes, keyed by the parse file name
+    replays: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl FixtureBackend {
+    /// Loads recorded data from a directory
+    ///
+    /// All files are optional:
+    /// * `wrs.json` - A JSON array of scores, as returned by Parse
+    /// * `leaderboards.json` - A JSON object of raw mapid > array of scores
+    /// * `weekly.json` - The raw Parse response for the `CHALLENGE_DATA` weekly stats
+    /// * `weekly_scores.json` - A JSON object of challenge id > array of scores
+    /// * `replays/` - Replay files, named after their parse file name
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let backend = FixtureBackend::default();
+
+        if let Ok(raw) = fs::read_to_string(dir.join("wrs.json")) {
+            for score in serde_json::from_str::<Vec<Score>>(&raw)? {
+                backend.set_wr(score);
+            }
+        }
+
+        if let Ok(raw) = fs::read_to_string(dir.join("leaderboards.json")) {
+            for (map_id, scores) in serde_json::from_str::<HashMap<String, Vec<Score>>>(&raw)? {
+                backend.set_leaderboard(map_id, scores);
+            }
+        }
+
+        if let Ok(raw) = fs::read_to_string(dir.join("weekly.json")) {
+            let weekly = Weekly::from_str(&raw)
+                .map_err(|err| anyhow!("Failed to load weekly fixture: {}", err))?;
+            backend.set_weekly(weekly);
+        }
+
+        if let Ok(raw) = fs::read_to_string(dir.join("weekly_scores.json")) {
+            for (challenge_id, scores) in serde_json::from_str::<HashMap<String, Vec<Score>>>(&raw)?
+            {
+                backend.set_weekly_scores(challenge_id, scores);
+            }
+        }
+
+        if let Ok(entries) = fs::read_dir(dir.join("replays")) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                backend.set_replay(name, fs::read(entry.path())?);
+            }
+        }
+
+        Ok(backend)
+    }
+
+    /// Sets the world record for the scores level
+    pub fn set_wr(&self, score: Score) {
+        self.wrs
+            .write()
+            .unwrap()
+            .insert(score.map_id.clone(), score);
+    }
+
+    /// Sets the full leaderboard for a raw mapid (`SP_###`)
+    pub fn set_leaderboard(&self, map_id: String, scores: Vec<Score>) {
+        self.leaderboards.write().unwrap().insert(map_id, scores);
+    }
+
+    /// Sets the weekly challenge stats
+    pub fn set_weekly(&self, weekly: Weekly) {
+        *self.weekly.write().unwrap() = Some(weekly);
+    }
+
+    /// Sets the scores for a challenge, in level order
+    pub fn set_weekly_scores(&self, challenge_id: String, scores: Vec<Score>) {
+        self.weekly_scores
+            .write()
+            .unwrap()
+            .insert(challenge_id, scores);
+    }
+
+    /// Sets the bytes for a replay file
+    pub fn set_replay(&self, name: String, bytes: Vec<u8>) {
+        self.replays.write().unwrap().insert(name, bytes);
+    }
+}
+
+#[async_trait]
+impl LeaderboardBackend for FixtureBackend {
+    async fn fetch_wr(&self, level: &str) -> Result<Score> {
+        self.wrs
+            .read()
+            .unwrap()
+            .get(&format!("SP_{}", level))
+            .cloned()
+            .ok_or(ParseError::Empty(format!("SP_{}", level)).into())
+    }
+
+    async fn fetch_wr_candidates(&self, bounds: &[(String, f32)]) -> Result<Vec<Score>> {
+        let wrs = self.wrs.read().unwrap();
+
+        Ok(bounds
+            .iter()
+            .filter_map(|(level, max_time)| {
+                wrs.get(&format!("SP_{}", level))
+                    .filter(|score| score.time <= *max_time)
+                    .cloned()
+            })
+            .collect())
+    }
+
+    async fn fetch_level_ids(&self) -> Result<Vec<String>> {
+        let mut levels: Vec<String> = self
+            .wrs
+            .read()
+            .unwrap()
+            .keys()
+            .filter_map(|map_id| map_id.strip_prefix("SP_"))
+            .map(String::from)
+            .collect();
+        levels.sort();
+
+        Ok(levels)
+    }
+
+    async fn fetch_top(&self, level: &str, limit: u32) -> Result<Vec<Score>> {
+        let map_id = format!("SP_{}", level);
+
+        let leaderboard = self.leaderboards.read().unwrap().get(&map_id).cloned();
+        let mut scores = match leaderboard {
+            Some(scores) => scores,
+            // Falls back to only the world record if no leaderboard is set
+            None => vec![self.fetch_wr(level).await?],
+        };
+        scores.sort_by(|a, b| a.time.total_cmp(&b.time));
+        scores.truncate(limit as usize);
+
+        Ok(scores)
+    }
+
+    async fn fetch_weekly(&self) -> Result<Weekly> {
+        self.weekly
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(anyhow!("No fixture weekly data"))
+    }
+
+    async fn fetch_weekly_scores(&self, challenge: &Challenge) -> Result<Vec<Score>> {
+        self.weekly_scores
+            .read()
+            .unwrap()
+            .get(&challenge.challenge_id)
+            .cloned()
+            .ok_or(anyhow!(
+                "No fixture scores for challenge: {}",
+                challenge.challenge_id
+            ))
+    }
+
+    async fn download_replay(&self, replay: &Replay) -> Result<Vec<u8>> {
+        self.replays
+            .read()
+            .unwrap()
+            .get(&replay.name)
+            .cloned()
+            .ok_or(anyhow!("No fixture replay for: {}", replay.name))
+    }
+}
+
+#[tokio::test]
+async fn test_fixture_wr() {
+    use crate::test_util::get_fake_score;
+
+    let backend = FixtureBackend::default();
+    let mut score = get_fake_score(10.0..15.0);
+    score.map_id = "SP_test".into();
+    backend.set_wr(score.clone());
+
+    assert_eq!(backend.fetch_wr("test").await.unwrap().time, score.time);
+    assert!(backend.fetch_wr("missing").await.is_err());
+}
@@ -0,0 +1,66 @@
+//! Abstracts where leaderboard data comes from
+//!
+//! The real implementation talks to the Parse backend,
+//! while the fixture one serves recorded data from memory for offline runs
+
+pub mod fixture;
+pub mod parse;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+
+use crate::{
+    backend::{fixture::FixtureBackend, parse::ParseBackend},
+    config::SETTINGS,
+    miu::{
+        score::{Replay, Score},
+        weekly_data::{Challenge, Weekly},
+    },
+};
+
+/// Everything the checker needs to ask a leaderboard backend
+#[async_trait]
+pub trait LeaderboardBackend: Send + Sync {
+    /// Fetches the current world record for a level id, *without the `SP_` prefix*
+    async fn fetch_wr(&self, level: &str) -> Result<Score>;
+
+    /// Fetches every score at or below the given time for many levels at once
+    ///
+    /// `bounds` is `(level id, max time)`, the results are not grouped or complete,
+    /// callers should fall back to `fetch_wr` for any level missing from them
+    async fn fetch_wr_candidates(&self, _bounds: &[(String, f32)]) -> Result<Vec<Score>> {
+        Err(anyhow!("Batched world record fetching is not supported"))
+    }
+
+    /// Fetches level ids that have scores on the backend, *without the `SP_` prefix*
+    ///
+    /// Used to discover new levels, so it doesn't have to return every level
+    async fn fetch_level_ids(&self) -> Result<Vec<String>> {
+        Err(anyhow!("Level discovery is not supported"))
+    }
+
+    /// Fetches the top `limit` scores for a level id, ordered by rank
+    async fn fetch_top(&self, level: &str, limit: u32) -> Result<Vec<Score>>;
+
+    /// Fetches the weekly challenge stats, containing the current and previous challenge
+    async fn fetch_weekly(&self) -> Result<Weekly>;
+
+    /// Fetches the best score for every level in a challenge, in level order
+    async fn fetch_weekly_scores(&self, challenge: &Challenge) -> Result<Vec<Score>>;
+
+    /// Downloads the raw bytes of a replay file
+    async fn download_replay(&self, replay: &Replay) -> Result<Vec<u8>>;
+}
+
+/// Creates the backend specified in the settings
+///
+/// Uses the fixture backend if `fixture_dir` is set, otherwise Parse
+pub fn from_settings(client: &Client) -> Result<Box<dyn LeaderboardBackend>> {
+    let fixture_dir = SETTINGS.read().unwrap().fixture_dir.clone();
+
+    Ok(match fixture_dir {
+        Some(dir) => Box::new(FixtureBackend::load(dir)?),
+        None => Box::new(ParseBackend::new(client.clone())),
+    })
+}
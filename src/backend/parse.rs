@@ -0,0 +1,193 @@
+//! The real Parse backend used in production
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+
+use crate::{
+    backend::LeaderboardBackend,
+    config::SETTINGS,
+    miu::{
+        score::{Replay, Score},
+        weekly_data::{Challenge, Weekly},
+    },
+    request::{
+        error::ParseError, get_base_url, make_paginated_request, make_request, query::ParseQuery,
+        raw_request,
+    },
+};
+
+/// A score with only its map id, from a `keys` query
+#[derive(Debug, Deserialize)]
+struct MapId {
+    #[serde(rename = "mapID")]
+    map_id: String,
+}
+
+/// Fetches everything from the MIU Parse backend
+///
+/// Points to `https://{domain}` unless `parse.base_url` is set in the settings
+pub struct ParseBackend {
+    client: Client,
+}
+
+impl ParseBackend {
+    /// Creates a new Parse backend using the given client
+    pub fn new(client: Client) -> Self {
+        ParseBackend { client }
+    }
+}
+
+#[async_trait]
+impl LeaderboardBackend for ParseBackend {
+    async fn fetch_wr(&self, level: &str) -> Result<Score> {
+        let query = ParseQuery::new()
+            .equal_to("mapID", format!("SP_{}", level))
+            .order("time")
+            .order_desc("updatedAt")
+            .limit(1);
+
+        let mut scores = make_request::<Score>(&self.client, &query, None, None)
+            .await
+            .with_context(|| format!("[{}] Failed to fetch score", level))?
+            .results
+            .unwrap_or_default();
+
+        if scores.is_empty() {
+            return Err(ParseError::Empty(format!("SP_{}", level)).into());
+        }
+
+        Ok(scores.remove(0))
+    }
+
+    async fn fetch_wr_candidates(&self, bounds: &[(String, f32)]) -> Result<Vec<Score>> {
+        // Small margin so f32 rounding never excludes the actual world record
+        let conditions: Vec<ParseQuery> = bounds
+            .iter()
+            .map(|(level, max_time)| {
+                ParseQuery::new()
+                    .equal_to("mapID", format!("SP_{}", level))
+                    .less_than_or_equal_to("time", max_time + 0.001)
+            })
+            .collect();
+
+        let query = ParseQuery::new()
+            .or(conditions)
+            .order("time")
+            .order_desc("updatedAt")
+            .limit(1000);
+
+        Ok(make_request::<Score>(&self.client, &query, None, None)
+            .await
+            .context("Failed to fetch batched scores")?
+            .results
+            .unwrap_or_default())
+    }
+
+    async fn fetch_level_ids(&self) -> Result<Vec<String>> {
+        // Only looks at the most recent scores, any new level will show up there quickly
+        let query = ParseQuery::new()
+            .order_desc("updatedAt")
+            .keys(&["mapID"])
+            .limit(1000);
+
+        let scores = make_request::<MapId>(&self.client, &query, None, None)
+            .await
+            .context("Failed to fetch recent map ids")?
+            .results
+            .unwrap_or_default();
+
+        let mut levels: Vec<String> = vec![];
+        for score in scores {
+            if let Some(level) = score.map_id.strip_prefix("SP_") {
+                if !levels.iter().any(|l| l == level) {
+                    levels.push(level.to_owned());
+                }
+            }
+        }
+
+        Ok(levels)
+    }
+
+    async fn fetch_top(&self, level: &str, limit: u32) -> Result<Vec<Score>> {
+        let query = ParseQuery::new()
+            .equal_to("mapID", format!("SP_{}", level))
+            .order("time")
+            .order_desc("updatedAt")
+            .limit(limit.min(1000));
+
+        // Parse caps a single request at 1000 results
+        make_paginated_request(&self.client, &query, None, None, Some(limit as usize))
+            .await
+            .with_context(|| format!("[{}] Failed to fetch top scores", level))
+    }
+
+    async fn fetch_weekly(&self) -> Result<Weekly> {
+        Weekly::fetch(&self.client).await
+    }
+
+    async fn fetch_weekly_scores(&self, challenge: &Challenge) -> Result<Vec<Score>> {
+        let (start, end) = (challenge.start_date, challenge.end_date);
+        let class_name = SETTINGS.read().unwrap().parse.weekly.class_name.clone();
+
+        let mut level_futures = Vec::with_capacity(challenge.levels.len());
+
+        for i in 0..challenge.levels.len() {
+            let map_id = format!("{}{}", challenge.chapter_set, i);
+            let query = ParseQuery::new()
+                .equal_to("mapID", map_id.as_str())
+                .date_range("updatedAt", start, end)
+                .order("time")
+                .limit(1);
+
+            let class_name = class_name.clone();
+            level_futures.push(async move {
+                let mut scores =
+                    make_request::<Score>(&self.client, &query, None, Some(class_name))
+                        .await
+                        .context("Failed to fetch weekly")?
+                        .results
+                        .unwrap_or_default();
+
+                if scores.is_empty() {
+                    return Err(ParseError::Empty(map_id).into());
+                }
+
+                Ok(scores.remove(0))
+            });
+        }
+
+        // Ran concurrently, the scheduler keeps the request budget
+        try_join_all(level_futures).await
+    }
+
+    async fn download_replay(&self, replay: &Replay) -> Result<Vec<u8>> {
+        let appid = SETTINGS.read().unwrap().parse.appid.clone();
+
+        let url = match Url::parse(&format!(
+            "{}/parse/files/{}/{}",
+            get_base_url(),
+            appid,
+            replay.name
+        )) {
+            Ok(url) => url,
+            Err(err) => return Err(anyhow!("Failed to parse replay url: {}", err)),
+        };
+
+        let res = raw_request(&self.client, url)
+            .await
+            .context("Failed to download replay")?;
+
+        if !res.status.is_success() {
+            return Err(ParseError::Status {
+                status: res.status,
+                retry_after: None,
+            })
+            .context("Failed to download replay");
+        }
+
+        Ok(res.body)
+    }
+}
@@ -0,0 +1,460 @@
+//! Command line arguments and the smaller one-off subcommands
+//!
+//! The main loop itself lives in `lib.rs`
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use reqwest::Client;
+
+use crate::{
+    backend,
+    config::{error::ConfigError, load_settings, SETTINGS},
+    db::{self, create_tables, migrations, setup, RecordStatus},
+    discord::webhook,
+    metadata::load_levels,
+    miu::{self, score::Score},
+    new_wr,
+};
+
+/// MIUU WR Checker, checks the MIUU backend for new world records and weekly challenges
+#[derive(Debug, Parser)]
+#[command(author, version, about)]
+pub struct Cli {
+    /// The subcommand to run, defaults to `run`
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Writes all webhooks as JSON instead of sending them to Discord
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// The config file to read, `config.*` in the working directory if not given
+    #[arg(long, global = true, env = "MIUU_CONFIG")]
+    pub config: Option<String>,
+
+    /// Overrides a config value, like `--set parse.appid=abc`, can be given multiple times
+    ///
+    /// Lists like `discord.webhooks` are comma separated
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true, value_parser = parse_override)]
+    pub overrides: Vec<(String, String)>,
+}
+
+/// Parses a `key=value` config override
+fn parse_override(raw: &str) -> Result<(String, String)> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or(anyhow!("Expected KEY=VALUE, got: {}", raw))?;
+
+    Ok((key.trim().to_owned(), value.trim().to_owned()))
+}
+
+/// All subcommands
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Runs the WR checking loop forever
+    Run,
+    /// Runs a single WR checking iteration and then exits
+    CheckOnce,
+    /// Creates all database tables and exits
+    InitDb,
+    /// Inserts the current backend world records that the database is missing, without announcing them
+    Backfill,
+    /// Exports the world record history for every level as JSON
+    Export {
+        /// File to write to, prints to stdout if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Posts a world record recap for the last few days
+    PostRecap {
+        /// How many days back the recap covers
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+    },
+    /// Shows the saved world record for a level id
+    ShowWr {
+        /// The level id, without the `SP_` prefix
+        level: String,
+    },
+    /// Lists or applies database schema migrations
+    Migrate {
+        /// What to do with the migrations
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Lists, approves or rejects world records held for review
+    Review {
+        /// What to do with the held world records
+        #[command(subcommand)]
+        action: ReviewAction,
+    },
+    /// Checks the config file and reports every problem found
+    ValidateConfig,
+}
+
+/// Actions for the `migrate` subcommand
+#[derive(Debug, Subcommand)]
+pub enum MigrateAction {
+    /// Lists every migration and if it has been applied
+    List,
+    /// Applies pending migrations
+    Run {
+        /// Only migrate up to and including this version
+        #[arg(long)]
+        to: Option<u32>,
+    },
+}
+
+/// Actions for the `review` subcommand
+#[derive(Debug, Subcommand)]
+pub enum ReviewAction {
+    /// Lists every world record waiting for review
+    List,
+    /// Approves a held world record and announces it
+    Approve {
+        /// The record id, as shown by `review list`
+        id: i64,
+    },
+    /// Rejects a held world record, it will never be announced
+    Reject {
+        /// The record id, as shown by `review list`
+        id: i64,
+    },
+}
+
+/// Creates all database tables
+pub async fn init_db() -> Result<()> {
+    let mut conn = setup().await;
+    create_tables(&mut conn).await;
+
+    println!("{}", "Database initialized".green().bold());
+
+    Ok(())
+}
+
+/// Fetches the current world record for every level
+///
+/// And saves the ones that are faster than, or missing from, the database
+pub async fn backfill() -> Result<()> {
+    let client = Client::new();
+    let backend = backend::from_settings(&client)?;
+    let mut conn = setup().await;
+
+    create_tables(&mut conn).await;
+    let levels = load_levels(&mut conn).await?;
+
+    let mut count: u32 = 0;
+    for level in &levels.active_ids() {
+        let score = match backend.fetch_wr(level).await {
+            Ok(score) => score,
+            Err(err) => {
+                println!("{}: {:#}", "Failed to fetch WR".red().bold(), err);
+                continue;
+            }
+        };
+
+        if let Some(saved) = db::get_wr(&mut conn, level).await? {
+            if score.time >= saved.time {
+                continue;
+            }
+        }
+
+        println!(
+            "{}: {} ({}, {})",
+            "Backfilling".green().bold(),
+            level,
+            score.time,
+            score.username
+        );
+        new_wr(&*backend, &mut conn, score, &[]).await;
+        count += 1;
+    }
+
+    println!("{} {} levels", "Backfilled".green().bold(), count);
+
+    Ok(())
+}
+
+/// Exports every levels world record history as a JSON object of level id > scores
+pub async fn export(output: Option<PathBuf>) -> Result<()> {
+    let mut conn = setup().await;
+    create_tables(&mut conn).await;
+
+    let mut history: BTreeMap<String, Vec<Score>> = BTreeMap::new();
+    for level in load_levels(&mut conn).await?.ids() {
+        let scores = db::get_history(&mut conn, &level).await?;
+        history.insert(level, scores);
+    }
+
+    let json = serde_json::to_string_pretty(&history)?;
+    match output {
+        Some(path) => {
+            fs::write(&path, json)?;
+            println!("{}: {}", "Exported to".green().bold(), path.display());
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
+
+/// Posts a world record recap covering the last `days` days
+pub async fn post_recap(days: i64) -> Result<()> {
+    let mut conn = setup().await;
+    create_tables(&mut conn).await;
+
+    let duration = chrono::Duration::days(days);
+    let levels = load_levels(&mut conn).await?;
+    let scores = match db::get_latest_world_records(&mut conn, duration, &levels).await {
+        Some(scores) => scores,
+        None => {
+            println!("No new world records in the last {} days", days);
+            return Ok(());
+        }
+    };
+
+    let now = chrono::Utc::now();
+    let sent = miu::weekly_recap(&Client::new(), scores, (now - duration, now))
+        .await
+        .len();
+
+    if SETTINGS.read().unwrap().discord.dry_run {
+        println!("{}", "Dry run, the recap was not sent".yellow().bold());
+    } else {
+        println!("{} to {} webhook(s)", "Posted recap".green().bold(), sent);
+    }
+
+    Ok(())
+}
+
+/// Loads and validates the config file, printing every problem found
+pub fn validate_config() -> Result<()> {
+    match load_settings() {
+        Ok(_) => {
+            println!("{}", "Config is valid".green().bold());
+            Ok(())
+        }
+        Err(ConfigError::Invalid(problems)) => {
+            println!("{}", "Config is invalid".red().bold());
+            for problem in &problems {
+                println!("- {}", problem);
+            }
+
+            Err(anyhow!("Found {} problem(s) in the config", problems.len()))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Prints the saved world record for a level
+pub async fn show_wr(level: &str) -> Result<()> {
+    let mut conn = setup().await;
+    create_tables(&mut conn).await;
+
+    let score = db::get_wr(&mut conn, level)
+        .await?
+        .ok_or(anyhow!("No saved world record for: {}", level))?;
+
+    let title = load_levels(&mut conn).await?.get_title(level);
+
+    println!(
+        "{}: {}\n{} by {} ({})\nSet at: {}",
+        "World Record".green().bold(),
+        title,
+        score.get_formatted_time(),
+        score.username,
+        score.platform,
+        score.updated_at
+    );
+
+    Ok(())
+}
+
+/// Lists or applies database schema migrations
+pub async fn migrate(action: MigrateAction) -> Result<()> {
+    let mut conn = setup().await;
+
+    match action {
+        MigrateAction::List => {
+            let applied = migrations::applied(&mut conn).await?;
+
+            for migration in migrations::MIGRATIONS {
+                match applied.iter().find(|a| a.version == migration.version) {
+                    Some(a) => println!(
+                        "{} {:0>3} {} ({})",
+                        "[applied]".green(),
+                        migration.version,
+                        migration.name,
+                        a.applied_at
+                    ),
+                    None => println!(
+                        "{} {:0>3} {}",
+                        "[pending]".yellow(),
+                        migration.version,
+                        migration.name
+                    ),
+                }
+            }
+        }
+        MigrateAction::Run { to } => {
+            let applied = migrations::run(&mut conn, to).await?;
+
+            println!(
+                "{} {} migration(s)",
+                "Applied".green().bold(),
+                applied.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists, approves or rejects world records held for review
+pub async fn review(action: ReviewAction) -> Result<()> {
+    let mut conn = setup().await;
+    create_tables(&mut conn).await;
+
+    let (id, status) = match action {
+        ReviewAction::List => {
+            let pending = db::get_pending_reviews(&mut conn).await?;
+            if pending.is_empty() {
+                println!("No world records waiting for review");
+            }
+
+            for review in pending {
+                println!(
+                    "{} {} {} by {} ({}), {}",
+                    format!("[{}]", review.record_id).yellow(),
+                    review.score.map_id,
+                    review.score.get_formatted_time(),
+                    review.score.username,
+                    review.score.platform,
+                    review.reasons.join(", ")
+                );
+            }
+
+            return Ok(());
+        }
+        ReviewAction::Approve { id } => (id, RecordStatus::Confirmed),
+        ReviewAction::Reject { id } => (id, RecordStatus::Rejected),
+    };
+
+    let pending = db::get_pending_reviews(&mut conn)
+        .await?
+        .into_iter()
+        .find(|r| r.record_id == id)
+        .ok_or(anyhow!("No pending record with id: {}", id))?;
+    let level = pending.score.map_id.clone();
+    let previous = db::get_wr(&mut conn, &level).await?;
+
+    db::set_record_status(&mut conn, id, status).await?;
+
+    println!(
+        "{} record {} as {}",
+        "Marked".green().bold(),
+        id,
+        status.as_str()
+    );
+
+    // Announced here, since the running loop has already seen the score
+    if status == RecordStatus::Confirmed {
+        let mut score = pending.score;
+        score.map_id = format!("SP_{}", level);
+
+        // A level without a confirmed world record is announced against an empty score
+        let previous = match previous {
+            Some(previous) if score.time >= previous.time => return Ok(()),
+            Some(previous) => previous,
+            None => Score::empty(&score),
+        };
+
+        webhook::send_webhooks(
+            &Client::new(),
+            &[(score, previous)],
+            &load_levels(&mut conn).await?,
+        )
+        .await;
+        println!("{}", "Announced world record".green().bold());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_commands() {
+    let parse = |args: &[&str]| Cli::try_parse_from([&["miuu"], args].concat());
+
+    assert!(parse(&[]).unwrap().command.is_none());
+    assert!(matches!(
+        parse(&["check-once"]).unwrap().command,
+        Some(Command::CheckOnce)
+    ));
+    assert!(matches!(
+        parse(&["backfill"]).unwrap().command,
+        Some(Command::Backfill)
+    ));
+    assert!(matches!(
+        parse(&["export"]).unwrap().command,
+        Some(Command::Export { output: None })
+    ));
+    assert!(matches!(
+        parse(&["export", "-o", "history.json"]).unwrap().command,
+        Some(Command::Export { output: Some(path) }) if path == std::path::Path::new("history.json")
+    ));
+    assert!(matches!(
+        parse(&["post-recap"]).unwrap().command,
+        Some(Command::PostRecap { days: 7 })
+    ));
+    assert!(matches!(
+        parse(&["post-recap", "--days", "14"]).unwrap().command,
+        Some(Command::PostRecap { days: 14 })
+    ));
+    assert!(matches!(
+        parse(&["show-wr", "bunny_slope"]).unwrap().command,
+        Some(Command::ShowWr { level }) if level == "bunny_slope"
+    ));
+    assert!(matches!(
+        parse(&["migrate", "run", "--to", "3"]).unwrap().command,
+        Some(Command::Migrate {
+            action: MigrateAction::Run { to: Some(3) }
+        })
+    ));
+    assert!(matches!(
+        parse(&["review", "approve", "12"]).unwrap().command,
+        Some(Command::Review {
+            action: ReviewAction::Approve { id: 12 }
+        })
+    ));
+    assert!(matches!(
+        parse(&["validate-config"]).unwrap().command,
+        Some(Command::ValidateConfig)
+    ));
+
+    // Global flags work after the subcommand too
+    let cli = parse(&["check-once", "--dry-run", "--set", "parse.appid = abc"]).unwrap();
+    assert!(cli.dry_run);
+    assert_eq!(
+        vec![(String::from("parse.appid"), String::from("abc"))],
+        cli.overrides
+    );
+
+    assert!(parse(&["show-wr"]).is_err());
+    assert!(parse(&["review", "approve", "not_a_number"]).is_err());
+    assert!(parse(&["--set", "no_equals_sign"]).is_err());
+    assert!(parse(&["unknown"]).is_err());
+}
+
+#[tokio::test]
+async fn test_check_once() {
+    crate::test_util::init_test_settings();
+
+    let cli = Cli::try_parse_from(["miuu", "check-once"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::CheckOnce)));
+
+    // The fixture world record gets seeded, then the iteration finds nothing new
+    crate::run(true).await.unwrap();
+}
@@ -0,0 +1,43 @@
+//! Errors returned when loading the settings
+
+use std::{error::Error, fmt};
+
+/// Everything that can go wrong when loading the settings
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file could not be found, read or deserialized
+    Load(::config::ConfigError),
+    /// The config file was read but has invalid values, contains every problem found
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Load(err) => write!(f, "Failed to load config: {}", err),
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid config, {} problem(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n- {}", problem)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+// The load error is already part of the message, so it isn't returned as the source
+impl Error for ConfigError {}
+
+impl From<::config::ConfigError> for ConfigError {
+    fn from(err: ::config::ConfigError) -> Self {
+        ConfigError::Load(err)
+    }
+}
+
+#[test]
+fn test_invalid_display() {
+    let err = ConfigError::Invalid(vec![String::from("a"), String::from("b")]);
+    assert_eq!("Invalid config, 2 problem(s):\n- a\n- b", err.to_string());
+}
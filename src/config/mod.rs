@@ -0,0 +1,567 @@
+//! Holds struct and the global static struct related to settings
+//!
+//! Read from the config.toml / config.json file, then `MIUU_*` environment variables,
+//! then `--set` overrides from the command line, each layer overriding the previous one
+
+pub mod error;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use config::{Config, Environment, Map, Source, Value, ValueKind};
+use lazy_static::lazy_static;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+lazy_static! {
+    /// Global `settings` across the entire program
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use miuu_wr_checker_rust::config::SETTINGS;
+    ///
+    /// let db_url = &SETTINGS.read().unwrap().database_url;
+    /// ```
+    ///
+    /// `start` loads the settings up front, so a broken config is reported before this panics
+    pub static ref SETTINGS: RwLock<Settings> =
+        RwLock::new(load_settings().unwrap_or_else(|err| panic!("{}", err)));
+
+    /// Where `SETTINGS` is loaded from, kept so reloading uses the same sources
+    static ref SOURCES: RwLock<ConfigSources> = RwLock::new(ConfigSources::default());
+}
+
+/// The default config file name, without an extension
+pub const CONFIG_NAME: &str = "config";
+
+/// Every config file extension that is supported
+pub const CONFIG_EXTENSIONS: [&str; 6] = ["toml", "json", "yaml", "yml", "ini", "ron"];
+
+/// The prefix for environment variables, `MIUU_PARSE__APPID` sets `parse.appid`
+pub const ENV_PREFIX: &str = "MIUU";
+
+/// Keys that are read as comma separated lists from environment variables and overrides
+const LIST_KEYS: [&str; 4] = [
+    "discord.webhooks",
+    "discord.weekly_webhooks",
+    "review.webhooks",
+    "review.allowed_platforms",
+];
+
+/// Everything the settings are loaded from, besides the environment
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    /// The config file, `config` with any supported extension if not set
+    ///
+    /// The file is only required if a path is given
+    pub path: Option<String>,
+    /// `key = value` overrides, like `("parse.appid", "abc")`
+    pub overrides: Vec<(String, String)>,
+}
+
+/// Sets where the settings are loaded from, must be called before `SETTINGS` is first used
+pub fn set_sources(sources: ConfigSources) {
+    *SOURCES.write().unwrap() = sources;
+}
+
+/// Reads and validates the settings from every source given to `set_sources`
+pub fn load_settings() -> Result<Settings, ConfigError> {
+    let sources = SOURCES.read().unwrap().clone();
+    Settings::load_from(&sources)
+}
+
+/// Gets every config file path that is read, used to watch them for changes
+pub fn get_config_paths() -> Vec<PathBuf> {
+    let path = SOURCES.read().unwrap().path.clone();
+
+    match path {
+        Some(path) if Path::new(&path).extension().is_some() => vec![PathBuf::from(path)],
+        path => {
+            let name = path.unwrap_or(String::from(CONFIG_NAME));
+            CONFIG_EXTENSIONS
+                .iter()
+                .map(|ext| PathBuf::from(format!("{}.{}", name, ext)))
+                .collect()
+        }
+    }
+}
+
+/// Gets the environment variable source, `MIUU_DISCORD__WEBHOOKS` sets `discord.webhooks`
+///
+/// Values are kept as strings, so an appid like `007123` isn't read as a number
+fn get_env_source() -> Environment {
+    Environment::with_prefix(ENV_PREFIX)
+        .prefix_separator("_")
+        .separator("__")
+}
+
+/// Environment variables with every key in `LIST_KEYS` split on commas
+///
+/// `Environment` only splits lists when it also parses every other value as a number or bool
+#[derive(Debug, Clone)]
+struct ListEnvironment(Environment);
+
+impl Source for ListEnvironment {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, config::ConfigError> {
+        let mut values = self.0.collect()?;
+
+        for key in LIST_KEYS {
+            if let Some(value) = values.remove(key) {
+                let origin = value.origin().map(String::from);
+                let list: Vec<Value> = value
+                    .into_string()?
+                    .split(',')
+                    .map(|item| Value::new(origin.as_ref(), item))
+                    .collect();
+                values.insert(
+                    key.to_owned(),
+                    Value::new(origin.as_ref(), ValueKind::Array(list)),
+                );
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// Contains all the settings from the config.toml file
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Settings {
+    /// The filepath or URL to the sqlite database
+    pub database_url: String,
+    /// How long to wait between each main iteration
+    pub loop_wait_seconds: u64,
+    /// Sends an uptime request to kuma if filled in
+    ///
+    /// Mostly for my own personal uptime dashboard
+    pub kuma_push_url: Option<String>,
+    /// Reads all leaderboard data from recorded fixtures in this directory instead of Parse
+    ///
+    /// See `backend::fixture::FixtureBackend::load` for the expected files
+    pub fixture_dir: Option<String>,
+    /// A struct that contains discord related settings
+    pub discord: Discord,
+    /// A struct that contains parse related settings
+    pub parse: Parse,
+    /// Takes periodic snapshots of the top scores for every level if filled in
+    pub snapshot: Option<Snapshot>,
+    /// Holds suspicious world records for review if filled in
+    pub review: Option<Review>,
+    /// Discovers new levels from the backend if filled in
+    pub discovery: Option<Discovery>,
+}
+
+impl Settings {
+    /// Reads, deserializes and validates a config file, with `MIUU_*` environment variables applied
+    ///
+    /// The path can be given without an extension, see `CONFIG_EXTENSIONS`
+    pub fn load(path: &str) -> Result<Settings, ConfigError> {
+        Settings::load_from(&ConfigSources {
+            path: Some(path.to_owned()),
+            overrides: vec![],
+        })
+    }
+
+    /// Reads, deserializes and validates the settings from the config file,
+    /// `MIUU_*` environment variables and then the overrides
+    pub fn load_from(sources: &ConfigSources) -> Result<Settings, ConfigError> {
+        Settings::build(sources, get_env_source())
+    }
+
+    fn build(sources: &ConfigSources, env: Environment) -> Result<Settings, ConfigError> {
+        let file = config::File::with_name(sources.path.as_deref().unwrap_or(CONFIG_NAME))
+            .required(sources.path.is_some());
+        let mut builder = Config::builder()
+            .add_source(file)
+            .add_source(ListEnvironment(env));
+
+        for (key, value) in &sources.overrides {
+            builder = match LIST_KEYS.contains(&key.as_str()) {
+                true => builder.set_override(
+                    key.as_str(),
+                    value.split(',').map(String::from).collect::<Vec<String>>(),
+                )?,
+                false => builder.set_override(key.as_str(), value.as_str())?,
+            };
+        }
+
+        let settings = builder.build()?.try_deserialize::<Settings>()?;
+
+        let problems = settings.validate();
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid(problems));
+        }
+
+        Ok(settings)
+    }
+
+    /// Checks every value that would only fail later on, returns all problems found
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = vec![];
+
+        if self.database_url.trim().is_empty() {
+            problems.push(String::from("database_url is empty"));
+        }
+        if self.loop_wait_seconds == 0 {
+            problems.push(String::from("loop_wait_seconds must be above 0"));
+        }
+        if let Some(url) = &self.kuma_push_url {
+            check_url(&mut problems, "kuma_push_url", url);
+        }
+
+        for (name, webhooks) in [
+            ("discord.webhooks", &self.discord.webhooks),
+            ("discord.weekly_webhooks", &self.discord.weekly_webhooks),
+        ] {
+            for (i, url) in webhooks.iter().enumerate() {
+                check_url(&mut problems, &format!("{}[{}]", name, i), url);
+            }
+        }
+
+        // The domain is only used if no base url is set
+        match &self.parse.base_url {
+            Some(base_url) => check_url(&mut problems, "parse.base_url", base_url),
+            None => {
+                if !is_valid_domain(&self.parse.domain) {
+                    problems.push(format!(
+                        "parse.domain is not a valid domain, expected something like `www.example.com`: {:?}",
+                        self.parse.domain
+                    ));
+                }
+            }
+        }
+        for (name, value) in [
+            ("parse.appid", &self.parse.appid),
+            ("parse.class_name", &self.parse.class_name),
+            ("parse.weekly.class_name", &self.parse.weekly.class_name),
+            (
+                "parse.weekly.class_name_stats",
+                &self.parse.weekly.class_name_stats,
+            ),
+        ] {
+            if value.trim().is_empty() {
+                problems.push(format!("{} is empty", name));
+            }
+        }
+        if self.parse.retry.base_delay_ms > self.parse.retry.max_delay_ms {
+            problems.push(String::from(
+                "parse.retry.base_delay_ms is above parse.retry.max_delay_ms",
+            ));
+        }
+        if self.parse.limits.max_concurrency == 0 {
+            problems.push(String::from("parse.limits.max_concurrency must be above 0"));
+        }
+
+        if let Some(snapshot) = &self.snapshot {
+            if snapshot.top_n == 0 {
+                problems.push(String::from("snapshot.top_n must be above 0"));
+            }
+            if snapshot.interval_seconds == 0 {
+                problems.push(String::from("snapshot.interval_seconds must be above 0"));
+            }
+        }
+        if let Some(review) = &self.review {
+            for (i, url) in review.webhooks.iter().enumerate() {
+                check_url(&mut problems, &format!("review.webhooks[{}]", i), url);
+            }
+            if review.max_improvement_percent.is_some_and(|p| p <= 0.0) {
+                problems.push(String::from(
+                    "review.max_improvement_percent must be above 0",
+                ));
+            }
+        }
+        if let Some(discovery) = &self.discovery {
+            if discovery.interval_seconds == 0 {
+                problems.push(String::from("discovery.interval_seconds must be above 0"));
+            }
+        }
+
+        problems
+    }
+}
+
+/// Adds a problem if the url isn't a valid `http` or `https` url
+fn check_url(problems: &mut Vec<String>, name: &str, url: &str) {
+    match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => (),
+        Ok(_) => problems.push(format!("{} is not an http(s) url: {}", name, url)),
+        Err(err) => problems.push(format!("{} is not a valid url ({}): {}", name, err, url)),
+    }
+}
+
+/// Checks if the domain is a bare host, like `www.example.com` or `localhost:1337`
+fn is_valid_domain(domain: &str) -> bool {
+    let (host, port) = match domain.split_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (domain, None),
+    };
+
+    !host.is_empty()
+        && !host.starts_with('.')
+        && !host.ends_with('.')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && port.is_none_or(|port| port.parse::<u16>().is_ok())
+}
+
+/// Holds discord related settings
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Discord {
+    /// A vec of discord webhook urls
+    ///
+    /// These urls are used for new world records
+    /// and world record recap webhooks
+    pub webhooks: Vec<String>,
+    /// A vec of discord webhook urls
+    ///
+    /// These are used for weekly challenge announcement posts
+    pub weekly_webhooks: Vec<String>,
+    /// Writes all webhook requests as JSON instead of sending them to Discord
+    ///
+    /// Can also be enabled with the `--dry-run` flag
+    #[serde(default)]
+    pub dry_run: bool,
+    /// The directory to write dry run webhook requests into
+    ///
+    /// Prints them to stdout if not set
+    pub dry_run_dir: Option<String>,
+    /// Posts a message to `webhooks` when a world record gets removed from the backend
+    #[serde(default)]
+    pub announce_rollbacks: bool,
+    /// Posts a message to `webhooks` when a different user ties a world record
+    #[serde(default)]
+    pub announce_ties: bool,
+}
+
+/// Holds leaderboard snapshot settings
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Snapshot {
+    /// How many of the top scores to save per level
+    pub top_n: u32,
+    /// How long to wait between each snapshot
+    pub interval_seconds: u64,
+}
+
+/// Holds new level discovery settings
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Discovery {
+    /// How long to wait between each discovery
+    pub interval_seconds: u64,
+    /// Posts a message to `discord.webhooks` for every new level
+    #[serde(default)]
+    pub announce: bool,
+}
+
+/// Holds the plausibility checks for new world records
+///
+/// A world record failing any check is saved as pending review
+/// and only posted to the review webhooks
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Review {
+    /// A vec of discord webhook urls, for moderators
+    pub webhooks: Vec<String>,
+    /// Flags records that improve the old world record by more than this percentage
+    pub max_improvement_percent: Option<f32>,
+    /// Level id > the fastest plausible time, records below it are flagged
+    #[serde(default)]
+    pub level_floors: HashMap<String, f32>,
+    /// Flags records set on any other platform, allows all if empty
+    #[serde(default)]
+    pub allowed_platforms: Vec<String>,
+    /// Flags records that don't have a replay
+    #[serde(default)]
+    pub require_replay: bool,
+}
+
+/// Holds parse related settings
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Parse {
+    /// The url base (Domain) for the miubackend
+    pub domain: String,
+    /// Overrides the `https://{domain}` url base
+    ///
+    /// Useful for pointing the checker at a local mock Parse server
+    pub base_url: Option<String>,
+    /// The appid used in request headers
+    pub appid: String,
+    /// The class name for normal MIU ingame leaderboards
+    pub class_name: String,
+    /// A struct that contains weekly challenge parse classes
+    pub weekly: ParseWeekly,
+    /// Retry settings for failed requests, optional
+    #[serde(default)]
+    pub retry: ParseRetry,
+    /// Limits for how many requests are sent, optional
+    #[serde(default)]
+    pub limits: ParseLimits,
+}
+
+/// Holds the request budget for parse requests
+///
+/// Every request goes through `scheduler::SCHEDULER`
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ParseLimits {
+    /// How many requests can be in flight at once
+    pub max_concurrency: usize,
+    /// How many requests can be sent per minute, 0 for no limit
+    pub requests_per_minute: u32,
+    /// How many levels are checked per batched world record request, 0 to disable batching
+    pub batch_size: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_concurrency: 8,
+            requests_per_minute: 240,
+            batch_size: 25,
+        }
+    }
+}
+
+/// Holds retry settings for parse requests
+///
+/// Delays doubles every retry, with some random jitter
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ParseRetry {
+    /// How many times a failed request is retried
+    pub max_retries: u32,
+    /// The delay before the first retry, in milliseconds
+    pub base_delay_ms: u64,
+    /// The longest delay between retries, in milliseconds
+    pub max_delay_ms: u64,
+}
+
+impl Default for ParseRetry {
+    fn default() -> Self {
+        ParseRetry {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+/// Holds parse settings related to weekly challenges
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ParseWeekly {
+    /// The class name for weekly challenge leaderboard class
+    pub class_name: String,
+    /// The class name for weekly challenge stats/data class
+    pub class_name_stats: String,
+}
+
+#[test]
+fn test_validate() {
+    let mut settings: Settings = Config::builder()
+        .add_source(config::File::from_str(
+            r#"
+            database_url = "./db.sqlite"
+            loop_wait_seconds = 120
+
+            [discord]
+            webhooks = ["https://discord.com/api/webhooks/1/a"]
+            weekly_webhooks = []
+
+            [parse]
+            domain = "www.example.com"
+            appid = "appid"
+            class_name = "class"
+
+            [parse.weekly]
+            class_name = "challenge"
+            class_name_stats = "challenge_stats"
+            "#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap()
+        .try_deserialize()
+        .unwrap();
+    assert!(settings.validate().is_empty());
+
+    settings.loop_wait_seconds = 0;
+    settings.discord.webhooks.push(String::from("not a url"));
+    settings.parse.domain = String::from("https://www.example.com/");
+
+    assert_eq!(3, settings.validate().len());
+
+    settings.parse.base_url = Some(String::from("http://localhost:1337"));
+    assert_eq!(2, settings.validate().len());
+
+    assert!(is_valid_domain("localhost:1337"));
+    assert!(!is_valid_domain("localhost:port"));
+}
+
+#[test]
+fn test_layered_sources() {
+    let dir = std::env::temp_dir().join(format!("miuu_config_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    std::fs::write(
+        &path,
+        r#"
+        database_url = "./db.sqlite"
+        loop_wait_seconds = 120
+
+        [discord]
+        webhooks = []
+        weekly_webhooks = []
+
+        [parse]
+        domain = "www.example.com"
+        appid = "from_file"
+        class_name = "class"
+
+        [parse.weekly]
+        class_name = "challenge"
+        class_name_stats = "challenge_stats"
+        "#,
+    )
+    .unwrap();
+
+    let env = get_env_source().source(Some(HashMap::from([
+        (String::from("MIUU_PARSE__APPID"), String::from("from_env")),
+        (String::from("MIUU_LOOP_WAIT_SECONDS"), String::from("60")),
+        (
+            String::from("MIUU_DISCORD__WEBHOOKS"),
+            String::from("https://example.com/a,https://example.com/b"),
+        ),
+    ])));
+    let sources = ConfigSources {
+        path: Some(path.to_string_lossy().to_string()),
+        overrides: vec![
+            (String::from("loop_wait_seconds"), String::from("30")),
+            (String::from("discord.dry_run"), String::from("true")),
+        ],
+    };
+
+    let settings = Settings::build(&sources, env).unwrap();
+    assert_eq!("from_env", settings.parse.appid);
+    assert_eq!(30, settings.loop_wait_seconds);
+    assert_eq!(2, settings.discord.webhooks.len());
+    assert!(settings.discord.dry_run);
+
+    // Strings that look like numbers are kept as is
+    let env = get_env_source().source(Some(HashMap::from([(
+        String::from("MIUU_PARSE__APPID"),
+        String::from("007123"),
+    )])));
+    let settings = Settings::build(&sources, env).unwrap();
+    assert_eq!("007123", settings.parse.appid);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
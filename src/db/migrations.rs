@@ -0,0 +1,367 @@
+//! Versioned schema migrations
+//!
+//! Every applied migration is recorded in the `schema_migrations` table,
+//! and all pending ones are ran automatically upon init.
+//!
+//! To change the schema, add a new `Migration` to the end of `MIGRATIONS`,
+//! never edit one that has already been released.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use sqlx::{prelude::FromRow, Connection, SqliteConnection};
+
+/// A single schema change
+pub struct Migration {
+    /// The version, has to be unique and increasing
+    pub version: u32,
+    /// A short name describing the change
+    pub name: &'static str,
+    /// Returns the statements to run, given the level id of every `SP_###` table in the database
+    ///
+    /// Gets the level ids so migrations can alter every old per level table
+    pub statements: fn(&[String]) -> Vec<String>,
+}
+
+/// All migrations, in order
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        // IF NOT EXISTS since these existed before migrations did
+        statements: |_| {
+            vec![
+                String::from(
+                    r#"
+            CREATE TABLE IF NOT EXISTS metadata (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )"#,
+                ),
+                String::from(
+                    r#"
+            CREATE TABLE IF NOT EXISTS weekly_history (
+                start_date TEXT PRIMARY KEY,
+                end_date TEXT NOT NULL,
+                scores TEXT NOT NULL,
+                physics_mods TEXT NOT NULL,
+                name TEXT NOT NULL,
+                challenge_id TEXT NOT NULL
+            )"#,
+                ),
+            ]
+        },
+    },
+    Migration {
+        version: 2,
+        name: "snapshots",
+        statements: |_| {
+            vec![String::from(
+                r#"
+            CREATE TABLE IF NOT EXISTS snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                level TEXT NOT NULL,
+                takenAt TEXT NOT NULL,
+                rank INTEGER NOT NULL,
+                time REAL NOT NULL,
+                username TEXT NOT NULL,
+                userID TEXT NOT NULL,
+                skinUsed TEXT NOT NULL,
+                replayVersion INTEGER NOT NULL,
+                platform TEXT NOT NULL,
+                createdAt TEXT NOT NULL,
+                updatedAt TEXT NOT NULL
+            )"#,
+            )]
+        },
+    },
+    Migration {
+        version: 3,
+        name: "world_records",
+        // Copies every old SP_### table into a single table,
+        // the old tables are left untouched as a backup
+        statements: |levels| {
+            let mut statements = vec![
+                String::from(
+                    r#"
+            CREATE TABLE world_records (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                level TEXT NOT NULL,
+                time REAL NOT NULL,
+                username TEXT NOT NULL,
+                userID TEXT NOT NULL,
+                skinUsed TEXT NOT NULL,
+                replayVersion INTEGER NOT NULL,
+                platform TEXT NOT NULL,
+                createdAt TEXT NOT NULL,
+                updatedAt TEXT NOT NULL
+            )"#,
+                ),
+                String::from(
+                    "CREATE INDEX idx_world_records_level_time ON world_records (level, time)",
+                ),
+                String::from("CREATE INDEX idx_world_records_user ON world_records (userID)"),
+                String::from("CREATE INDEX idx_world_records_updated ON world_records (updatedAt)"),
+            ];
+
+            for level in levels {
+                statements.push(format!(
+                    r#"
+            INSERT INTO world_records (
+                level, time, username, userID, skinUsed, replayVersion, platform, createdAt, updatedAt
+            )
+            SELECT '{0}', time, username, userID, skinUsed, replayVersion, platform, createdAt, updatedAt
+            FROM SP_{0} ORDER BY id ASC"#,
+                    level
+                ));
+            }
+
+            statements
+        },
+    },
+    Migration {
+        version: 4,
+        name: "replays",
+        statements: |_| {
+            vec![
+                String::from(
+                    r#"
+            CREATE TABLE replays (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recordId INTEGER NOT NULL REFERENCES world_records (id),
+                path TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                sha256 TEXT NOT NULL,
+                parseName TEXT NOT NULL,
+                downloadedAt TEXT NOT NULL
+            )"#,
+                ),
+                String::from("CREATE INDEX idx_replays_record ON replays (recordId)"),
+            ]
+        },
+    },
+    Migration {
+        version: 5,
+        name: "record_status",
+        // Every existing record has already been announced, so they all stay confirmed
+        statements: |_| {
+            vec![
+                String::from(
+                    "ALTER TABLE world_records ADD COLUMN status TEXT NOT NULL DEFAULT 'confirmed'",
+                ),
+                String::from("ALTER TABLE world_records ADD COLUMN reviewReasons TEXT"),
+                String::from("CREATE INDEX idx_world_records_status ON world_records (status)"),
+            ]
+        },
+    },
+    Migration {
+        version: 6,
+        name: "record_removed_at",
+        statements: |_| {
+            vec![String::from(
+                "ALTER TABLE world_records ADD COLUMN removedAt TEXT",
+            )]
+        },
+    },
+    Migration {
+        version: 7,
+        name: "levels",
+        statements: |_| {
+            vec![String::from(
+                r#"
+            CREATE TABLE levels (
+                id TEXT PRIMARY KEY,
+                discoveredAt TEXT NOT NULL
+            )"#,
+            )]
+        },
+    },
+    Migration {
+        version: 8,
+        name: "snapshots_index",
+        statements: |_| {
+            vec![String::from(
+                "CREATE INDEX idx_snapshots_level_taken_at ON snapshots (level, takenAt)",
+            )]
+        },
+    },
+];
+
+/// A migration that has been applied to the database
+#[derive(Debug, FromRow)]
+pub struct AppliedMigration {
+    /// The version of the migration
+    pub version: u32,
+    /// The name of the migration
+    pub name: String,
+    /// When it was applied, in Utc
+    #[sqlx(rename = "appliedAt")]
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Returns the newest version in `MIGRATIONS`
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Gets all applied migrations, oldest first
+pub async fn applied(conn: &mut SqliteConnection) -> Result<Vec<AppliedMigration>> {
+    create_migrations_table(conn).await?;
+
+    Ok(
+        sqlx::query_as("SELECT * FROM schema_migrations ORDER BY version ASC")
+            .fetch_all(&mut *conn)
+            .await?,
+    )
+}
+
+/// Applies all pending migrations up to and including `target`, or all of them if `None`
+///
+/// Each migration runs in its own transaction, returns the versions that got applied
+pub async fn run(conn: &mut SqliteConnection, target: Option<u32>) -> Result<Vec<u32>> {
+    let current = applied(conn).await?.last().map(|m| m.version).unwrap_or(0);
+    let target = target.unwrap_or(latest_version());
+
+    if target < current {
+        return Err(anyhow!(
+            "Can't migrate backwards, database is at version {} but target is {}",
+            current,
+            target
+        ));
+    }
+
+    let levels = get_level_tables(conn).await?;
+    let mut versions: Vec<u32> = vec![];
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current && m.version <= target)
+    {
+        let mut tx = conn.begin().await?;
+
+        for statement in (migration.statements)(&levels) {
+            if let Err(err) = sqlx::query(&statement).execute(&mut *tx).await {
+                return Err(anyhow!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version,
+                    migration.name,
+                    err
+                ));
+            }
+        }
+
+        sqlx::query("INSERT INTO schema_migrations (version, name, appliedAt) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        println!(
+            "{}: {} ({})",
+            "Applied migration".green(),
+            migration.version,
+            migration.name
+        );
+        versions.push(migration.version);
+    }
+
+    Ok(versions)
+}
+
+/// Gets the level id of every old `SP_###` table
+async fn get_level_tables(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    let tables: Vec<(String,)> = sqlx::query_as(
+        r#"SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'SP\_%' ESCAPE '\'"#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(tables
+        .into_iter()
+        .map(|(name,)| name.trim_start_matches("SP_").to_string())
+        .collect())
+}
+
+async fn create_migrations_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            appliedAt TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[test]
+fn test_migration_order() {
+    for pair in MIGRATIONS.windows(2) {
+        assert!(pair[0].version < pair[1].version);
+    }
+}
+
+#[tokio::test]
+async fn test_run_migrations() {
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+
+    assert_eq!(vec![1], run(&mut conn, Some(1)).await.unwrap());
+    assert!(run(&mut conn, Some(0)).await.is_err());
+
+    run(&mut conn, None).await.unwrap();
+    assert!(run(&mut conn, None).await.unwrap().is_empty());
+    assert_eq!(
+        latest_version(),
+        applied(&mut conn).await.unwrap().last().unwrap().version
+    );
+}
+
+#[tokio::test]
+async fn test_world_records_migration() {
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    run(&mut conn, Some(2)).await.unwrap();
+
+    sqlx::query(
+        r#"
+        CREATE TABLE SP_test_level (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            time INTEGER NOT NULL,
+            username TEXT NOT NULL,
+            userID TEXT NOT NULL,
+            skinUsed TEXT NOT NULL,
+            replayVersion INTEGER NOT NULL,
+            platform TEXT NOT NULL,
+            createdAt TEXT NOT NULL,
+            updatedAt TEXT NOT NULL
+        )
+    "#,
+    )
+    .execute(&mut conn)
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO SP_test_level (time, username, userID, skinUsed, replayVersion, platform, createdAt, updatedAt)
+        VALUES (12.5, 'Username1', 'UserId1', 'swirl', 5, 'PC', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')
+    "#,
+    )
+    .execute(&mut conn)
+    .await
+    .unwrap();
+
+    run(&mut conn, None).await.unwrap();
+
+    let wr = crate::db::get_wr(&mut conn, "test_level")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(12.5, wr.time);
+    assert_eq!("Username1", wr.username);
+}
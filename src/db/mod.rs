@@ -0,0 +1,887 @@
+//! Handles the init connection and queries to the sqlite database
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use colored::Colorize;
+use sqlx::{prelude::FromRow, Connection, SqliteConnection};
+use std::{collections::HashMap, str::FromStr};
+
+pub mod migrations;
+
+use crate::{
+    config::SETTINGS,
+    metadata::Levels,
+    miu::{
+        replay::ReplayFile,
+        score::{RecapScore, Score},
+        snapshot::Snapshot,
+        weekly::WeeklyHistory,
+    },
+};
+
+/// Create all tables in the database
+///
+/// Runs all pending migrations, ran upon init.
+pub async fn create_tables(conn: &mut SqliteConnection) {
+    if let Err(err) = migrations::run(conn, None).await {
+        panic!("Failed to run migrations: {}", err);
+    }
+}
+
+/// Gets the current saved weekly challenge end date
+pub async fn get_current_weekly_end(conn: &mut SqliteConnection) -> Result<DateTime<Utc>> {
+    let date_text: DBWeekEnd =
+        sqlx::query_as("SELECT value FROM metadata WHERE key = \"curr_week_end\"")
+            .fetch_one(&mut *conn)
+            .await?;
+
+    Ok(date_text.value)
+}
+
+/// Upserts a weekly challenge end date into the metadata table
+pub async fn upsert_weekly_end(conn: &mut SqliteConnection, date: DateTime<Utc>) {
+    match sqlx::query(
+        r#"
+            INSERT INTO metadata 
+            (key, value) VALUES 
+            ("curr_week_end", ?) 
+            ON CONFLICT DO 
+            UPDATE SET value = ? 
+            WHERE key = "curr_week_end"
+        "#,
+    )
+    .bind(date)
+    .bind(date)
+    .execute(&mut *conn)
+    .await
+    {
+        Err(err) => panic!("Failed to upsert weekly end: {}", err),
+        _ => println!("{}", "Upserted weekly end".green()),
+    }
+}
+
+/// Saves a finished weekly challenge into the weekly history
+///
+/// Overwrites any existing entry with the same start date
+pub async fn upsert_weekly_history(
+    conn: &mut SqliteConnection,
+    history: &WeeklyHistory,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO weekly_history 
+        (start_date, end_date, scores, physics_mods, name, challenge_id) VALUES 
+        (?, ?, ?, ?, ?, ?) 
+        ON CONFLICT (start_date) DO 
+        UPDATE SET 
+            end_date = excluded.end_date,
+            scores = excluded.scores,
+            physics_mods = excluded.physics_mods,
+            name = excluded.name,
+            challenge_id = excluded.challenge_id
+    "#,
+    )
+    .bind(history.start_date)
+    .bind(history.end_date)
+    .bind(serde_json::to_string(&history.scores)?)
+    .bind(serde_json::to_string(&history.levels)?)
+    .bind(&history.name)
+    .bind(&history.challenge_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets every saved weekly challenge, newest first
+pub async fn get_weekly_history(conn: &mut SqliteConnection) -> Result<Vec<WeeklyHistory>> {
+    let rows: Vec<DBWeeklyHistory> =
+        sqlx::query_as("SELECT * FROM weekly_history ORDER BY start_date DESC")
+            .fetch_all(&mut *conn)
+            .await?;
+
+    rows.into_iter().map(|r| r.to_weekly_history()).collect()
+}
+
+/// Gets a saved weekly challenge by its challenge id
+pub async fn get_weekly_history_by_id(
+    conn: &mut SqliteConnection,
+    challenge_id: &str,
+) -> Result<Option<WeeklyHistory>> {
+    let row: Option<DBWeeklyHistory> =
+        sqlx::query_as("SELECT * FROM weekly_history WHERE challenge_id = ?")
+            .bind(challenge_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    row.map(|r| r.to_weekly_history()).transpose()
+}
+
+/// Gets all world records given a `Vec<String>` of level ids.
+///
+/// Levels without any records are left out
+pub async fn get_all(conn: &mut SqliteConnection, levels: &[String]) -> HashMap<String, Score> {
+    let db_scores: Vec<DBScore> = sqlx::query_as(
+        r#"
+        SELECT * FROM (
+            SELECT *, ROW_NUMBER() OVER (PARTITION BY level ORDER BY time ASC, id ASC) AS position
+            FROM world_records WHERE status = 'confirmed'
+        ) WHERE position = 1
+    "#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .expect("Failed to fetch latest wrs");
+
+    db_scores
+        .into_iter()
+        .filter(|s| levels.contains(&s.level))
+        .map(|s| (String::from("SP_") + &s.level, s.to_score()))
+        .collect()
+}
+
+/// Gets the current world record for a level
+///
+/// Returns `None` if the level has no records yet
+pub async fn get_wr(conn: &mut SqliteConnection, level: &str) -> Result<Option<Score>> {
+    let db_score: Option<DBScore> = sqlx::query_as(
+        r#"
+        SELECT * FROM world_records
+        WHERE level = ? AND status = 'confirmed'
+        ORDER BY time ASC LIMIT 1
+    "#,
+    )
+    .bind(level)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(db_score.map(|s| s.to_score()))
+}
+
+/// Gets every world record for a level, newest (fastest) first
+pub async fn get_history(conn: &mut SqliteConnection, level: &str) -> Result<Vec<Score>> {
+    let db_scores: Vec<DBScore> = sqlx::query_as(
+        "SELECT * FROM world_records WHERE level = ? AND status = 'confirmed' ORDER BY time ASC",
+    )
+    .bind(level)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(db_scores.into_iter().map(|s| s.to_score()).collect())
+}
+
+/// Gets every world record set by a user across all levels, newest first
+pub async fn get_user_records(conn: &mut SqliteConnection, user_id: &str) -> Result<Vec<Score>> {
+    let db_scores: Vec<DBScore> = sqlx::query_as(
+        r#"
+        SELECT * FROM world_records
+        WHERE userID = ? AND status = 'confirmed'
+        ORDER BY updatedAt DESC
+    "#,
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(db_scores.into_iter().map(|s| s.to_score()).collect())
+}
+
+/// Inserts a new world record into the levels history, given the score.
+///
+/// Returns the row id of the new record
+pub async fn update_level(conn: &mut SqliteConnection, score: &Score) -> Result<i64> {
+    insert_record(conn, score, RecordStatus::Confirmed, &[]).await
+}
+
+/// Inserts a world record with the given status, and the reasons if it was flagged for review
+///
+/// Returns the row id of the new record
+pub async fn insert_record(
+    conn: &mut SqliteConnection,
+    score: &Score,
+    status: RecordStatus,
+    reasons: &[String],
+) -> Result<i64> {
+    let reasons = match reasons.is_empty() {
+        true => None,
+        false => Some(serde_json::to_string(reasons)?),
+    };
+
+    //mhmhm i love those .bind, probably a way to bind a struct to values or somethning
+    match sqlx::query(
+        r#"
+    INSERT INTO world_records (
+        level,
+        time, 
+        username, 
+        userID, 
+        skinUsed, 
+        replayVersion, 
+        platform, 
+        createdAt, 
+        updatedAt,
+        status,
+        reviewReasons
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+    )
+    .bind(score.map_id.trim_start_matches("SP_"))
+    .bind(score.time)
+    .bind(score.username.clone())
+    .bind(score.user_id.clone())
+    .bind(score.skin_used.clone())
+    .bind(score.replay_version)
+    .bind(score.platform.clone())
+    .bind(score.created_at)
+    .bind(score.updated_at)
+    .bind(status.as_str())
+    .bind(reasons)
+    .execute(conn)
+    .await
+    {
+        Ok(res) => Ok(res.last_insert_rowid()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Finds a saved record of any status that matches the score exactly
+///
+/// Returns the row id and status of the record
+pub async fn find_record(
+    conn: &mut SqliteConnection,
+    score: &Score,
+) -> Result<Option<(i64, RecordStatus)>> {
+    let row: Option<(i64, String)> = sqlx::query_as(
+        r#"
+        SELECT id, status FROM world_records
+        WHERE level = ? AND userID = ? AND time = ? AND updatedAt = ?
+        ORDER BY id DESC LIMIT 1
+    "#,
+    )
+    .bind(score.map_id.trim_start_matches("SP_"))
+    .bind(&score.user_id)
+    .bind(score.time)
+    .bind(score.updated_at)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(match row {
+        Some((id, status)) => Some((id, status.parse()?)),
+        None => None,
+    })
+}
+
+/// Finds a saved record of any status by the same user with the same time
+///
+/// Unlike `find_record` the update date is ignored, so a resubmitted time still matches
+///
+/// Returns the row id and status of the record
+pub async fn find_record_by_time(
+    conn: &mut SqliteConnection,
+    score: &Score,
+) -> Result<Option<(i64, RecordStatus)>> {
+    let row: Option<(i64, String)> = sqlx::query_as(
+        r#"
+        SELECT id, status FROM world_records
+        WHERE level = ? AND userID = ? AND time = ?
+        ORDER BY id DESC LIMIT 1
+    "#,
+    )
+    .bind(score.map_id.trim_start_matches("SP_"))
+    .bind(&score.user_id)
+    .bind(score.time)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(match row {
+        Some((id, status)) => Some((id, status.parse()?)),
+        None => None,
+    })
+}
+
+/// Gets every record waiting for review, oldest first
+pub async fn get_pending_reviews(conn: &mut SqliteConnection) -> Result<Vec<PendingReview>> {
+    let rows: Vec<DBPendingReview> = sqlx::query_as(
+        "SELECT * FROM world_records WHERE status = 'pending' ORDER BY updatedAt ASC",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(PendingReview {
+                record_id: row.score.id,
+                score: row.score.to_score(),
+                reasons: match row.review_reasons {
+                    Some(reasons) => serde_json::from_str(&reasons)?,
+                    None => vec![],
+                },
+            })
+        })
+        .collect()
+}
+
+/// Sets the status of a record, returns false if no record has the id
+pub async fn set_record_status(
+    conn: &mut SqliteConnection,
+    record_id: i64,
+    status: RecordStatus,
+) -> Result<bool> {
+    let res = sqlx::query("UPDATE world_records SET status = ? WHERE id = ?")
+        .bind(status.as_str())
+        .bind(record_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Marks every confirmed record for a level faster than `time` as removed
+///
+/// Used when the backend no longer has them, returns the removed records
+pub async fn remove_records_faster_than(
+    conn: &mut SqliteConnection,
+    level: &str,
+    time: f32,
+) -> Result<Vec<Score>> {
+    let mut tx = conn.begin().await?;
+
+    let removed: Vec<DBScore> = sqlx::query_as(
+        "SELECT * FROM world_records WHERE level = ? AND status = 'confirmed' AND time < ? ORDER BY time ASC",
+    )
+    .bind(level)
+    .bind(time)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE world_records SET status = 'removed', removedAt = ?
+        WHERE level = ? AND status = 'confirmed' AND time < ?
+    "#,
+    )
+    .bind(Utc::now())
+    .bind(level)
+    .bind(time)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(removed.into_iter().map(|s| s.to_score()).collect())
+}
+
+/// Gets every level id discovered from the backend, oldest first
+pub async fn get_levels(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as("SELECT id FROM levels ORDER BY discoveredAt ASC")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Saves a level id discovered from the backend
+///
+/// Returns false if the level was already saved
+pub async fn insert_level(conn: &mut SqliteConnection, level: &str) -> Result<bool> {
+    let res = sqlx::query("INSERT OR IGNORE INTO levels (id, discoveredAt) VALUES (?, ?)")
+        .bind(level)
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Indexes a downloaded replay file for a world record
+pub async fn insert_replay(
+    conn: &mut SqliteConnection,
+    record_id: i64,
+    file: &ReplayFile,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO replays (
+            recordId,
+            path,
+            size,
+            sha256,
+            parseName,
+            downloadedAt
+        ) VALUES (?, ?, ?, ?, ?, ?)"#,
+    )
+    .bind(record_id)
+    .bind(&file.path)
+    .bind(file.size)
+    .bind(&file.sha256)
+    .bind(&file.parse_name)
+    .bind(file.downloaded_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets the newest indexed replay file for a world record
+pub async fn get_replay(conn: &mut SqliteConnection, record_id: i64) -> Result<Option<ReplayFile>> {
+    Ok(
+        sqlx::query_as("SELECT * FROM replays WHERE recordId = ? ORDER BY id DESC LIMIT 1")
+            .bind(record_id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+/// Gets all world records within a `chrono::Duration`.
+///
+/// And only for the levels given, the recap uses their titles
+pub async fn get_latest_world_records(
+    conn: &mut SqliteConnection,
+    duration: Duration,
+    levels: &Levels,
+) -> Option<Vec<RecapScore>> {
+    let break_point_date = Utc::now() - duration;
+
+    // Query could be optimized if we also stored unix times along side and only used a where clause,
+    // But since the times are stored as ISO text strings im unsure if we can do a where, so just doing them all for now
+    let all_scores: Vec<DBScore> = sqlx::query_as(
+        "SELECT * FROM world_records WHERE status = 'confirmed' ORDER BY level ASC, time ASC",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .expect("Failed to fetch latest wrs");
+
+    let mut level_map: HashMap<String, Vec<DBScore>> = HashMap::new();
+    for score in all_scores {
+        level_map
+            .entry(score.level.clone())
+            .or_default()
+            .push(score);
+    }
+
+    let mut scores: Vec<RecapScore> = vec![];
+
+    for level in levels.iter() {
+        let db_scores = match level_map.remove(&level.id) {
+            Some(db_scores) => db_scores,
+            None => continue,
+        };
+
+        // Edge case for only one, aka new wr. should only happen to new empty databases
+        if db_scores.len() == 1 {
+            let only = db_scores.first().unwrap();
+            if only.updated_at > break_point_date {
+                scores.push(RecapScore {
+                    level: level.title.clone(),
+                    improvement: 0.0,
+                    scores: vec![only.to_score()],
+                });
+            }
+
+            continue;
+        }
+
+        let mut level_scores: Vec<Score> = vec![];
+
+        for score in db_scores {
+            if score.updated_at < break_point_date {
+                let improv = match level_scores.first() {
+                    Some(newest) => score.time - newest.time,
+                    None => break,
+                };
+
+                let recap_score = RecapScore {
+                    level: level.title.clone(),
+                    improvement: improv,
+                    scores: level_scores,
+                };
+
+                scores.push(recap_score);
+
+                break; // Old times
+            }
+
+            level_scores.push(score.to_score());
+        }
+    }
+
+    if scores.is_empty() {
+        return None;
+    }
+
+    Some(scores)
+}
+
+/// Inserts a leaderboard snapshot for a level
+///
+/// The scores are expected to be ordered by rank
+pub async fn insert_snapshot(
+    conn: &mut SqliteConnection,
+    level: &str,
+    taken_at: DateTime<Utc>,
+    scores: &[Score],
+) -> Result<()> {
+    // A snapshot is only ever saved whole
+    let mut tx = conn.begin().await?;
+
+    for (i, score) in scores.iter().enumerate() {
+        sqlx::query(
+            r#"
+        INSERT INTO snapshots (
+            level,
+            takenAt,
+            rank,
+            time,
+            username,
+            userID,
+            skinUsed,
+            replayVersion,
+            platform,
+            createdAt,
+            updatedAt
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(level)
+        .bind(taken_at)
+        .bind(i as u32 + 1)
+        .bind(score.time)
+        .bind(&score.username)
+        .bind(&score.user_id)
+        .bind(&score.skin_used)
+        .bind(score.replay_version)
+        .bind(&score.platform)
+        .bind(score.created_at)
+        .bind(score.updated_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Gets all snapshots for a level taken after `since`, oldest first
+pub async fn get_snapshots(
+    conn: &mut SqliteConnection,
+    level: &str,
+    since: DateTime<Utc>,
+) -> Result<Vec<Snapshot>> {
+    let rows: Vec<DBSnapshotScore> = sqlx::query_as(
+        "SELECT * FROM snapshots WHERE level = ? AND takenAt >= ? ORDER BY takenAt ASC, rank ASC",
+    )
+    .bind(level)
+    .bind(since)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut snapshots: Vec<Snapshot> = vec![];
+    for row in rows {
+        let mut score = row.score.to_score();
+        // The id is the snapshot row, not a world record
+        score.record_id = None;
+
+        match snapshots.last_mut() {
+            Some(last) if last.taken_at == row.taken_at => last.scores.push(score),
+            _ => snapshots.push(Snapshot {
+                level: level.to_owned(),
+                taken_at: row.taken_at,
+                scores: vec![score],
+            }),
+        }
+    }
+
+    Ok(snapshots)
+}
+
+/// Establishes a connection to the database
+///
+/// Depending on the database_url set in the settings
+pub async fn setup() -> SqliteConnection {
+    let url = SETTINGS.read().unwrap().database_url.clone();
+
+    match SqliteConnection::connect(&url).await {
+        Ok(conn) => conn,
+        Err(err) => panic!("Failed to connect to database: {}", err),
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct DBScore {
+    id: i64,
+
+    level: String,
+    time: f32,
+    username: String,
+
+    #[sqlx(rename = "userID")]
+    user_id: String,
+
+    #[sqlx(rename = "skinUsed")]
+    skin_used: String,
+
+    #[sqlx(rename = "replayVersion")]
+    replay_version: u32,
+
+    platform: String,
+
+    #[sqlx(rename = "createdAt")]
+    created_at: DateTime<Utc>,
+
+    #[sqlx(rename = "updatedAt")]
+    updated_at: DateTime<Utc>,
+}
+
+impl DBScore {
+    fn to_score(&self) -> Score {
+        Score {
+            time: self.time,
+            username: self.username.clone(),
+            user_id: self.user_id.clone(),
+            skin_used: self.skin_used.clone(),
+            map_id: self.level.clone(),
+            replay_version: self.replay_version,
+            platform: self.platform.clone(),
+            replay: None,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            object_id: None,
+            record_id: Some(self.id),
+        }
+    }
+}
+
+/// The review state of a world record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordStatus {
+    /// A normal, announced world record
+    Confirmed,
+    /// Flagged as suspicious, waiting for a moderator
+    Pending,
+    /// Rejected by a moderator, never announced
+    Rejected,
+    /// Was the world record, but got removed from the backend
+    Removed,
+    /// Tied the world record, by a different user than the holder
+    Tied,
+}
+
+impl RecordStatus {
+    /// The value stored in the `status` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordStatus::Confirmed => "confirmed",
+            RecordStatus::Pending => "pending",
+            RecordStatus::Rejected => "rejected",
+            RecordStatus::Removed => "removed",
+            RecordStatus::Tied => "tied",
+        }
+    }
+}
+
+impl FromStr for RecordStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "confirmed" => Ok(RecordStatus::Confirmed),
+            "pending" => Ok(RecordStatus::Pending),
+            "rejected" => Ok(RecordStatus::Rejected),
+            "removed" => Ok(RecordStatus::Removed),
+            "tied" => Ok(RecordStatus::Tied),
+            _ => Err(anyhow!("Unknown record status: {}", s)),
+        }
+    }
+}
+
+/// A world record waiting for a moderator
+#[derive(Debug, Clone)]
+pub struct PendingReview {
+    /// The row id in the `world_records` table
+    pub record_id: i64,
+    /// The flagged score
+    pub score: Score,
+    /// Why the score was flagged
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, FromRow)]
+struct DBPendingReview {
+    #[sqlx(flatten)]
+    score: DBScore,
+
+    #[sqlx(rename = "reviewReasons")]
+    review_reasons: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+struct DBSnapshotScore {
+    #[sqlx(flatten)]
+    score: DBScore,
+
+    #[sqlx(rename = "takenAt")]
+    taken_at: DateTime<Utc>,
+}
+
+/// `physics_mods` holds every challenge level alongside its physics mods, as JSON
+#[derive(Debug, FromRow)]
+struct DBWeeklyHistory {
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    scores: String,
+    physics_mods: String,
+    name: String,
+    challenge_id: String,
+}
+
+impl DBWeeklyHistory {
+    fn to_weekly_history(&self) -> Result<WeeklyHistory> {
+        Ok(WeeklyHistory {
+            challenge_id: self.challenge_id.clone(),
+            name: self.name.clone(),
+            start_date: self.start_date,
+            end_date: self.end_date,
+            levels: serde_json::from_str(&self.physics_mods)?,
+            scores: serde_json::from_str(&self.scores)?,
+        })
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct DBWeekEnd {
+    pub value: DateTime<Utc>,
+}
+
+#[tokio::test]
+async fn test_snapshot_roundtrip() {
+    use crate::test_util::get_fake_score;
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let scores = vec![get_fake_score(5.0..6.0), get_fake_score(6.0..7.0)];
+    insert_snapshot(&mut conn, "test_level", Utc::now(), &scores)
+        .await
+        .unwrap();
+
+    let snapshots = get_snapshots(&mut conn, "test_level", Utc::now() - Duration::days(1))
+        .await
+        .unwrap();
+
+    assert_eq!(1, snapshots.len());
+    assert_eq!(scores[1].time, snapshots[0].scores[1].time);
+}
+
+#[tokio::test]
+async fn test_weekly_history_roundtrip() {
+    use crate::{
+        miu::weekly_data::{ChallengeLevel, PhysicsMod},
+        test_util::get_fake_score,
+    };
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let mut history = WeeklyHistory {
+        challenge_id: "challenge_1".into(),
+        name: "Test Challenge".into(),
+        start_date: Utc::now() - Duration::days(7),
+        end_date: Utc::now(),
+        levels: vec![ChallengeLevel {
+            name: "Test Level".into(),
+            id: "A0".into(),
+            physicsmod: vec![PhysicsMod::Gravity(0.5), PhysicsMod::NoGems(true)],
+        }],
+        scores: vec![get_fake_score(5.0..6.0)],
+    };
+    upsert_weekly_history(&mut conn, &history).await.unwrap();
+
+    history.name = "Renamed".into();
+    upsert_weekly_history(&mut conn, &history).await.unwrap();
+
+    let saved = get_weekly_history(&mut conn).await.unwrap();
+    assert_eq!(1, saved.len());
+    assert_eq!("Renamed", saved[0].name);
+    assert_eq!(history.scores[0].time, saved[0].scores[0].time);
+    assert_eq!(2, saved[0].levels[0].physicsmod.len());
+
+    assert!(get_weekly_history_by_id(&mut conn, "challenge_1")
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test]
+async fn test_pending_review() {
+    use crate::test_util::get_fake_score;
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let mut confirmed = get_fake_score(10.0..11.0);
+    confirmed.map_id = String::from("SP_test_level");
+    update_level(&mut conn, &confirmed).await.unwrap();
+
+    let mut flagged = get_fake_score(1.0..2.0);
+    flagged.map_id = String::from("SP_test_level");
+    let id = insert_record(
+        &mut conn,
+        &flagged,
+        RecordStatus::Pending,
+        &[String::from("Too fast")],
+    )
+    .await
+    .unwrap();
+
+    // Pending records are never seen as the world record
+    let wr = get_wr(&mut conn, "test_level").await.unwrap().unwrap();
+    assert_eq!(confirmed.time, wr.time);
+
+    let pending = get_pending_reviews(&mut conn).await.unwrap();
+    assert_eq!(1, pending.len());
+    assert_eq!(vec![String::from("Too fast")], pending[0].reasons);
+    assert_eq!(
+        Some((id, RecordStatus::Pending)),
+        find_record(&mut conn, &flagged).await.unwrap()
+    );
+
+    assert!(set_record_status(&mut conn, id, RecordStatus::Confirmed)
+        .await
+        .unwrap());
+    let wr = get_wr(&mut conn, "test_level").await.unwrap().unwrap();
+    assert_eq!(flagged.time, wr.time);
+}
+
+#[tokio::test]
+async fn test_remove_records() {
+    use crate::test_util::get_fake_score;
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    for time in [12.0, 10.0, 8.0] {
+        let mut score = get_fake_score(1.0..2.0);
+        score.map_id = String::from("SP_test_level");
+        score.time = time;
+        update_level(&mut conn, &score).await.unwrap();
+    }
+
+    let removed = remove_records_faster_than(&mut conn, "test_level", 11.0)
+        .await
+        .unwrap();
+    assert_eq!(2, removed.len());
+
+    let wr = get_wr(&mut conn, "test_level").await.unwrap().unwrap();
+    assert_eq!(12.0, wr.time);
+    assert_eq!(1, get_history(&mut conn, "test_level").await.unwrap().len());
+}
+
+#[tokio::test]
+async fn test_levels() {
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    assert!(insert_level(&mut conn, "new_level").await.unwrap());
+    assert!(!insert_level(&mut conn, "new_level").await.unwrap());
+
+    assert_eq!(vec!["new_level"], get_levels(&mut conn).await.unwrap());
+}
@@ -3,9 +3,12 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
-use crate::miu::{
-    score::{RecapScore, Score},
-    weekly_data::{Challenge, NameLang, Weekly},
+use crate::{
+    metadata::Level,
+    miu::{
+        score::{RecapScore, Score},
+        weekly_data::{Challenge, NameLang, Weekly},
+    },
 };
 
 /// Gets the default footer for all embeds
@@ -24,22 +27,58 @@ const DISCORDPFP: &str = "https://cdn.discordapp.com/attachments/365772775832420
 const THUMBNAILURL: &str =
     "https://cdn.discordapp.com/emojis/592218899441909760.webp?size=96&quality=lossless";
 
+/// Gets the thumbnail for a level, the default one if the level doesn't have one
+fn get_level_thumbnail(level: &Level) -> Thumbnail {
+    Thumbnail {
+        url: level
+            .thumbnail_url
+            .clone()
+            .unwrap_or(String::from(THUMBNAILURL)),
+    }
+}
+
+/// Gets a field with every medal time of a level, with the medal earned by `time` in bold
+///
+/// Returns `None` if the level has no medal times
+pub fn get_medal_field(level: &Level, time: f32) -> Option<Field> {
+    let medal_times = level.get_medal_times();
+    if medal_times.is_empty() {
+        return None;
+    }
+
+    let earned = level.get_medal(time);
+    let value = medal_times
+        .into_iter()
+        .map(|(medal, medal_time)| match Some(medal) == earned {
+            true => format!("**{}: {}**", medal, medal_time),
+            false => format!("{}: {}", medal, medal_time),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    Some(Field {
+        name: String::from("Medals:"),
+        value,
+        inline: true,
+    })
+}
+
 /// Gets an embed for world record announcements
-pub fn get_score_embed(new: &Score, prev: &Score, level_title: String) -> Embed {
-    Embed {
+///
+/// Includes the medal times of the level if it has any
+pub fn get_score_embed(new: &Score, prev: &Score, level: &Level) -> Embed {
+    let mut embed = Embed {
         r#type: String::from("rich"),
         title: String::from("***New Ultra World Record!***"),
         description: format!(
             "Level: **{}**\nImprovement: -**{:.6}**",
-            level_title,
+            level.title,
             prev.time - new.time
         ),
         color: 15844367,
         timestamp: new.updated_at,
         footer: get_default_footer(),
-        thumbnail: Some(Thumbnail {
-            url: String::from(THUMBNAILURL),
-        }),
+        thumbnail: Some(get_level_thumbnail(level)),
         image: None,
         fields: vec![
             Field {
@@ -63,11 +102,110 @@ pub fn get_score_embed(new: &Score, prev: &Score, level_title: String) -> Embed
                 inline: true,
             },
         ],
+    };
+
+    if let Some(field) = get_medal_field(level, new.time) {
+        embed.fields.push(field);
+    }
+
+    embed
+}
+
+/// Gets an embed for a world record that was held for review
+///
+/// Same as the announcement embed, but with the reasons it was flagged
+pub fn get_review_embed(
+    new: &Score,
+    prev: &Score,
+    level: &Level,
+    record_id: Option<i64>,
+    reasons: &[String],
+) -> Embed {
+    let mut embed = get_score_embed(new, prev, level);
+
+    embed.title = String::from("***World Record Pending Review***");
+    embed.color = 15548997;
+    if let Some(record_id) = record_id {
+        embed.description += &format!("\nRecord Id: **{}**", record_id);
     }
+    embed.fields.push(Field {
+        name: String::from("Flagged:"),
+        value: reasons
+            .iter()
+            .map(|r| format!("- {}", r))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        inline: false,
+    });
+
+    embed
+}
+
+/// Gets an embed for a newly discovered level, with its current world record if it has one
+pub fn get_new_level_embed(level: &Level, wr: Option<&Score>) -> Embed {
+    let mut fields = match wr {
+        Some(wr) => vec![Field {
+            name: String::from("World Record:"),
+            value: format!(
+                "{}\n{}\n{}\n",
+                wr.get_formatted_time(),
+                wr.username,
+                wr.platform
+            ),
+            inline: true,
+        }],
+        None => vec![],
+    };
+    if let Some(field) = wr.and_then(|wr| get_medal_field(level, wr.time)) {
+        fields.push(field);
+    }
+
+    Embed {
+        r#type: String::from("rich"),
+        title: String::from("***New Level Added!***"),
+        description: format!("Level: **{}**", level.title),
+        color: 5763719,
+        timestamp: Utc::now(),
+        footer: get_default_footer(),
+        thumbnail: Some(get_level_thumbnail(level)),
+        image: None,
+        fields,
+    }
+}
+
+/// Gets an embed for a world record being tied by a different user
+pub fn get_tie_embed(tie: &Score, holder: &Score, level: &Level) -> Embed {
+    let mut embed = get_score_embed(tie, holder, level);
+
+    embed.title = String::from("***World Record Tied!***");
+    embed.description = format!("Level: **{}**", level.title);
+    embed.color = 3447003;
+    embed.fields[0].name = String::from("Tied By:");
+    embed.fields[1].name = String::from("Held By:");
+
+    embed
+}
+
+/// Gets an embed for a world record that was removed from the backend
+pub fn get_rollback_embed(removed: &Score, new_best: &Score, level: &Level) -> Embed {
+    let mut embed = get_score_embed(new_best, removed, level);
+
+    embed.title = String::from("***World Record Removed***");
+    embed.description = format!(
+        "Level: **{}**\nRolled back: +**{:.6}**",
+        level.title,
+        new_best.time - removed.time
+    );
+    embed.color = 9807270;
+    embed.timestamp = Utc::now();
+    embed.fields[0].name = String::from("Current:");
+    embed.fields[1].name = String::from("Removed:");
+
+    embed
 }
 
 /// Gets an embed for the weekly challenge announcement post
-pub fn get_weekly_embed(weekly: &Weekly, previous_scores: &Vec<Score>) -> Embed {
+pub fn get_weekly_embed(weekly: &Weekly, previous_scores: &[Score]) -> Embed {
     fn get_physics_mods(challenge: &Challenge) -> Vec<String> {
         challenge
             .levels
@@ -93,7 +231,7 @@ pub fn get_weekly_embed(weekly: &Weekly, previous_scores: &Vec<Score>) -> Embed
         .collect::<Vec<String>>();
 
     let mut prev_fields: Vec<Field> = vec![];
-    for (i, score) in previous_scores.into_iter().enumerate() {
+    for (i, score) in previous_scores.iter().enumerate() {
         let level = weekly.score_buckets.previous.levels[i].name.clone();
 
         prev_fields.push(Field {
@@ -121,7 +259,7 @@ pub fn get_weekly_embed(weekly: &Weekly, previous_scores: &Vec<Score>) -> Embed
         },
         Field {
             name: String::from("Previous Challenge:"),
-            value: format!("{}", weekly.score_buckets.previous.get_name(NameLang::En)),
+            value: weekly.score_buckets.previous.get_name(NameLang::En),
             inline: false,
         },
         Field {
@@ -288,8 +426,41 @@ fn test_score_embed() {
     assert!(!get_score_embed(
         &get_fake_score(5.0..7.0),
         &get_fake_score(7.0..11.0),
-        "Test Level".into()
+        &Level::new("test_level")
     )
     .title
     .is_empty());
 }
+
+#[test]
+fn test_tie_embed() {
+    use crate::test_util::get_fake_score;
+
+    let holder = get_fake_score(5.0..7.0);
+    let mut tie = get_fake_score(5.0..7.0);
+    tie.time = holder.time;
+
+    let mut level = Level::new("test_level");
+    level.title = String::from("Test Level");
+
+    let embed = get_tie_embed(&tie, &holder, &level);
+    assert_eq!("Level: **Test Level**", embed.description);
+    assert_eq!(2, embed.fields.len());
+}
+
+#[test]
+fn test_medal_field() {
+    use crate::test_util::get_fake_score;
+
+    let mut level = Level::new("test_level");
+    let new = get_fake_score(5.0..7.0);
+    let prev = get_fake_score(7.0..11.0);
+    assert_eq!(2, get_score_embed(&new, &prev, &level).fields.len());
+
+    level.diamond_time = Some(7.0);
+    level.par_time = Some(20.0);
+
+    let embed = get_score_embed(&new, &prev, &level);
+    assert_eq!(3, embed.fields.len());
+    assert_eq!("**Diamond: 7**\nPar: 20", embed.fields[2].value);
+}
@@ -1,58 +1,172 @@
 //! Used to send webhooks to Discords API
 
-use std::collections::HashMap;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
+use anyhow::Result;
+use chrono::Utc;
 use colored::Colorize;
 use reqwest::{header::CONTENT_TYPE, Client};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     config::SETTINGS,
-    discord::embed::{get_score_embed, get_weekly_embed, Embed},
+    discord::embed::{
+        get_new_level_embed, get_review_embed, get_rollback_embed, get_score_embed, get_tie_embed,
+        get_weekly_embed, Embed,
+    },
+    metadata::{Level, Levels},
     miu::{score::Score, weekly_data::Weekly},
 };
 
 /// Sends World Record announcement message(s)
 ///
-/// Given the tuple of scores, (`Vec<(new, previous)`)
+/// Given the tuple of scores, (`Vec<(new, previous)`), returns the sent message ids
 pub async fn send_webhooks(
     client: &Client,
-    scores: Vec<(Score, Score)>,
-    name_conversion: &HashMap<String, String>,
-) {
+    scores: &[(Score, Score)],
+    levels: &Levels,
+) -> Vec<String> {
+    let mut ids: Vec<String> = vec![];
+
     for chunk in scores.chunks(10) {
         let mut request_data: WebhookRequest = WebhookRequest { embeds: vec![] };
 
         for (new, prev) in chunk {
-            let level_title = if let Some(name) = name_conversion.get(&new.map_id[3..]) {
-                name
-            } else {
-                println!("{}: {}", "Failed to convert level name".red(), new.map_id);
-                &new.map_id
-            }
-            .to_owned();
+            let level = match levels.get(&new.map_id[3..]) {
+                Some(level) => level.clone(),
+                None => {
+                    println!("{}: {}", "Failed to convert level name".red(), new.map_id);
+                    Level::new(&new.map_id)
+                }
+            };
 
-            request_data
-                .embeds
-                .push(get_score_embed(new, prev, level_title))
+            request_data.embeds.push(get_score_embed(new, prev, &level))
         }
 
-        send_to_all_webhooks(client, &request_data).await;
+        ids.append(&mut send_to_all_webhooks(client, &request_data).await);
     }
+
+    ids
+}
+
+/// Sends a world record that was held for review to all webhooks in `SETTINGS.review.webhooks`
+///
+/// Returns the sent message ids
+pub async fn send_review_webhook(
+    client: &Client,
+    new: &Score,
+    prev: &Score,
+    level: &Level,
+    reasons: &[String],
+) -> Vec<String> {
+    let webhooks = match &SETTINGS.read().unwrap().review {
+        Some(review) => review.webhooks.clone(),
+        None => return vec![],
+    };
+
+    let request = WebhookRequest {
+        embeds: vec![get_review_embed(new, prev, level, new.record_id, reasons)],
+    };
+
+    send_to_webhooks(client, webhooks, "review_webhooks", &request).await
+}
+
+/// Sends a new level message to all webhooks in `SETTINGS.discord.webhooks`
+///
+/// Returns the sent message ids
+pub async fn send_new_level_webhook(
+    client: &Client,
+    level: &Level,
+    wr: Option<&Score>,
+) -> Vec<String> {
+    let request = WebhookRequest {
+        embeds: vec![get_new_level_embed(level, wr)],
+    };
+
+    send_to_all_webhooks(client, &request).await
+}
+
+/// Sends a world record tie message to all webhooks in `SETTINGS.discord.webhooks`
+///
+/// Returns the sent message ids
+pub async fn send_tie_webhook(
+    client: &Client,
+    tie: &Score,
+    holder: &Score,
+    level: &Level,
+) -> Vec<String> {
+    let request = WebhookRequest {
+        embeds: vec![get_tie_embed(tie, holder, level)],
+    };
+
+    send_to_all_webhooks(client, &request).await
+}
+
+/// Sends a world record removal message to all webhooks in `SETTINGS.discord.webhooks`
+///
+/// Returns the sent message ids
+pub async fn send_rollback_webhook(
+    client: &Client,
+    removed: &Score,
+    new_best: &Score,
+    level: &Level,
+) -> Vec<String> {
+    let request = WebhookRequest {
+        embeds: vec![get_rollback_embed(removed, new_best, level)],
+    };
+
+    send_to_all_webhooks(client, &request).await
 }
 
 /// Sends an embed to all webhooks in `SETTINGS.discord.webhooks`
+///
+/// Only writes the request as JSON if `SETTINGS.discord.dry_run` is set
 pub async fn send_to_all_webhooks(client: &Client, embeds: &WebhookRequest) -> Vec<String> {
+    let webhooks = SETTINGS.read().unwrap().discord.webhooks.clone();
+
+    send_to_webhooks(client, webhooks, "webhooks", embeds).await
+}
+
+/// Sends an embed to the given webhooks, `target` names the list for dry runs
+async fn send_to_webhooks(
+    client: &Client,
+    webhooks: Vec<String>,
+    target: &str,
+    embeds: &WebhookRequest,
+) -> Vec<String> {
+    if try_dry_run(target, embeds) {
+        return vec![];
+    }
+
     let mut ids: Vec<String> = vec![];
 
-    for url in &SETTINGS.read().unwrap().discord.webhooks {
-        let response: WebhookResponse = match client
+    for url in webhooks {
+        let response = client
             .post(url.to_owned() + "?wait=true")
             .json(embeds)
             .header(CONTENT_TYPE, "application/json")
             .send()
             .await
-        {
+            .and_then(|res| res.error_for_status());
+
+        // Only successful responses count as sent
+        let response: WebhookResponse = match response {
+            Ok(res) => match res.json::<WebhookResponse>().await {
+                Ok(response) => response,
+                Err(err) => {
+                    println!(
+                        "{}: {}, {}",
+                        "Failed to read webhook response".red().bold(),
+                        url,
+                        err
+                    );
+                    continue;
+                }
+            },
             Err(err) => {
                 println!(
                     "{}: {}, {}",
@@ -62,10 +176,6 @@ pub async fn send_to_all_webhooks(client: &Client, embeds: &WebhookRequest) -> V
                 );
                 continue;
             }
-            Ok(res) => res
-                .json::<WebhookResponse>()
-                .await
-                .expect("Failed to get webhook response"),
         };
 
         ids.push(response.id);
@@ -75,26 +185,92 @@ pub async fn send_to_all_webhooks(client: &Client, embeds: &WebhookRequest) -> V
 }
 
 /// Sends a weekly announcement embed to all webhooks in `SETTINGS.discord.weekly_webhooks`
-pub async fn send_weekly_embed(client: &Client, weekly: &Weekly, previous_scores: &Vec<Score>) {
-    let embed = get_weekly_embed(&weekly, &previous_scores);
-    let request_struct = WebhookRequest {
-        embeds: vec![embed],
+///
+/// Returns how many webhooks it was successfully sent to
+pub async fn send_weekly_embed(
+    client: &Client,
+    weekly: &Weekly,
+    previous_scores: &[Score],
+) -> usize {
+    let request = WebhookRequest {
+        embeds: vec![get_weekly_embed(weekly, previous_scores)],
     };
 
-    for url in &SETTINGS.read().unwrap().discord.weekly_webhooks {
-        match client
-            .post(url.clone())
-            .json(&request_struct)
-            .header(CONTENT_TYPE, "application/json")
-            .send()
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => println!("Failed to send challenge webhook"),
-        };
+    let weekly_webhooks = SETTINGS.read().unwrap().discord.weekly_webhooks.clone();
+
+    send_to_webhooks(client, weekly_webhooks, "weekly_webhooks", &request)
+        .await
+        .len()
+}
+
+/// Writes the request instead of sending it, if dry run is enabled
+///
+/// Returns true if it was a dry run and nothing should be sent
+fn try_dry_run(target: &str, request: &WebhookRequest) -> bool {
+    let (dry_run, dir) = {
+        let discord = &SETTINGS.read().unwrap().discord;
+        (discord.dry_run, discord.dry_run_dir.clone())
+    };
+
+    if !dry_run {
+        return false;
     }
+
+    match write_dry_run(dir.as_deref().map(Path::new), target, request) {
+        Ok(Some(path)) => println!(
+            "{}: {}",
+            "Dry run, wrote webhook to".yellow().bold(),
+            path.display()
+        ),
+        Ok(None) => (),
+        Err(err) => println!(
+            "{}: {}",
+            "Failed to write dry run webhook".red().bold(),
+            err
+        ),
+    }
+
+    true
 }
 
+/// Writes a webhook request as JSON into `dir`, or stdout if `None`
+///
+/// `target` is the webhook list it would have been sent to,
+/// and is used in the file name alongside a timestamp
+///
+/// Returns the path of the written file, if any
+pub fn write_dry_run(
+    dir: Option<&Path>,
+    target: &str,
+    request: &WebhookRequest,
+) -> Result<Option<PathBuf>> {
+    let json = serde_json::to_string_pretty(request)?;
+
+    let dir = match dir {
+        Some(dir) => dir,
+        None => {
+            println!("[{}] {}", target.bright_black(), json);
+            return Ok(None);
+        }
+    };
+
+    fs::create_dir_all(dir)?;
+
+    let count = DRY_RUN_COUNT.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(format!(
+        "{}_{:0>4}_{}.json",
+        Utc::now().format("%Y%m%d_%H%M%S"),
+        count,
+        target
+    ));
+    fs::write(&path, json)?;
+
+    Ok(Some(path))
+}
+
+/// Keeps dry run file names unique within the same second
+static DRY_RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 /// Webhook request, does not contain all Discord documented fields
 #[derive(Debug, Serialize)]
 pub struct WebhookRequest {
@@ -108,3 +284,50 @@ struct WebhookResponse {
     //...
     // https://discord.com/developers/docs/resources/channel#message-object
 }
+
+#[test]
+fn test_write_dry_run() {
+    let dir = std::env::temp_dir().join("miuu_dry_run_test");
+    let request = WebhookRequest { embeds: vec![] };
+
+    let path = write_dry_run(Some(&dir), "webhooks", &request)
+        .unwrap()
+        .unwrap();
+
+    assert!(path.starts_with(&dir));
+    assert!(path.to_string_lossy().ends_with("_webhooks.json"));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        serde_json::to_string_pretty(&request).unwrap()
+    );
+
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_error_status_not_sent() {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    crate::test_util::init_test_settings();
+
+    // Answers every request with a 404, like a deleted webhook
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 2\r\n\r\n{}")
+                .await;
+        }
+    });
+
+    let request = WebhookRequest { embeds: vec![] };
+    let ids = send_to_webhooks(&Client::new(), vec![url], "webhooks", &request).await;
+
+    assert!(ids.is_empty());
+}
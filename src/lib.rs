@@ -4,189 +4,1392 @@
 
 use std::{
     collections::HashMap,
-    thread::sleep,
     time::{Duration, Instant},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use clap::Parser;
 use colored::*;
 use reqwest::Client;
 use sqlx::SqliteConnection;
+use tokio::time::sleep;
 
 use crate::{
+    backend::LeaderboardBackend,
+    cli::{Cli, Command},
     db::*,
     discord::webhook::*,
     metadata::*,
     miu::{
         get_wrs,
         replay::download_replay,
+        review::check_plausibility,
         score::Score,
-        weekly::{check, fetch, WeekState},
-        weekly_data::NameLang,
+        snapshot::take_snapshots,
+        weekly::{check, fetch, WeekState, WeeklyHistory},
+        weekly_data::{NameLang, Weekly},
     },
+    reload::{reload, Reloader},
+    request::error::ParseError,
 };
 
+pub mod backend;
+pub mod cli;
 pub mod config;
 pub mod db;
 pub mod discord;
 pub mod metadata;
 pub mod miu;
+pub mod reload;
 pub mod request;
+pub mod scheduler;
 pub mod test_util;
 
 /// Main function for the program
+///
+/// Parses the command line and runs the given subcommand, defaults to `run`
 #[tokio::main]
 pub async fn start() -> Result<()> {
+    let args = Cli::parse();
+    let command = args.command.unwrap_or(Command::Run);
+
+    let mut overrides = args.overrides;
+    if args.dry_run {
+        overrides.push((String::from("discord.dry_run"), String::from("true")));
+    }
+    config::set_sources(config::ConfigSources {
+        path: args.config,
+        overrides,
+    });
+
+    // Loaded up front so a broken config is reported as an error, instead of panicking on first use
+    if !matches!(command, Command::ValidateConfig) {
+        config::load_settings()?;
+    }
+
+    match command {
+        Command::Run => run(false).await,
+        Command::CheckOnce => run(true).await,
+        Command::InitDb => cli::init_db().await,
+        Command::Backfill => cli::backfill().await,
+        Command::Export { output } => cli::export(output).await,
+        Command::PostRecap { days } => cli::post_recap(days).await,
+        Command::ShowWr { level } => cli::show_wr(&level).await,
+        Command::Migrate { action } => cli::migrate(action).await,
+        Command::Review { action } => cli::review(action).await,
+        Command::ValidateConfig => cli::validate_config(),
+    }
+}
+
+/// The main WR checking loop
+///
+/// Only runs a single iteration if `once` is true
+pub async fn run(once: bool) -> Result<()> {
     println!(
         "{} {}",
         "Starting MIU WRChecker".bold(),
         "(Rust Edition)".bright_black()
     );
 
-    let level_ids = load_name_vec();
-    let level_titles = load_name_conversion_map();
-
     let client = Client::new();
-    let mut conn = setup().await;
-
-    create_tables(&mut conn, &level_ids).await;
+    let backend = backend::from_settings(&client)?;
+    let mut state = State::new(client, backend).await?;
+    seed_missing_wrs(&mut state).await;
 
     println!("- {}", "Init Sequence Finished".green().bold());
 
-    let mut confirmed_wrs: HashMap<String, Score> = get_all(&mut conn, &level_ids).await;
-
-    let sleep_wait = Duration::from_secs(config::SETTINGS.read().unwrap().loop_wait_seconds);
-    let mut iter_count: u32 = 0;
+    let mut reloader = Reloader::start();
     loop {
-        let start = Instant::now();
+        if reloader.should_reload() {
+            reload(&mut state).await;
+        }
 
-        let new_scores = match get_wrs(&client, &level_ids).await {
-            Ok(scores) => scores,
-            Err(err) => {
-                println!("{}: {}", "Failed to get new WRs".red().bold(), err);
+        let report = run_iteration(&mut state).await;
+
+        let kuma_push_url = config::SETTINGS.read().unwrap().kuma_push_url.clone();
+        if let Some(kuma_url) = kuma_push_url {
+            match &state.client.get(kuma_url).send().await {
+                Ok(_) => println!("{}", "Successfully sent a kuma push".green()),
+                Err(err) => println!("{}: {}", "Failed to send a kuma push".red(), err),
+            }
+        }
+
+        if once {
+            if !report.errors.is_empty() {
+                return Err(anyhow!(
+                    "Iteration finished with {} error(s)",
+                    report.errors.len()
+                ));
+            }
+
+            return Ok(());
+        }
+
+        // Read every iteration, since the config can be reloaded
+        let sleep_wait = Duration::from_secs(config::SETTINGS.read().unwrap().loop_wait_seconds);
+        sleep(sleep_wait).await;
+    }
+}
+
+/// Everything that is kept between iterations
+pub struct State {
+    /// Client used for Discord webhooks and pushes
+    pub client: Client,
+    /// Where all leaderboard data is fetched from
+    pub backend: Box<dyn LeaderboardBackend>,
+    /// The database connection
+    pub conn: SqliteConnection,
+    /// Every known level, from the metadata file and discovered from the backend
+    pub levels: Levels,
+    /// The currently known world records, keyed by the raw mapid (`SP_###`)
+    pub confirmed_wrs: HashMap<String, Score>,
+    /// Backend bests that are held for review or rejected, keyed by the raw mapid (`SP_###`)
+    ///
+    /// Only kept for levels without a confirmed world record, so they aren't reported every iteration
+    pub unconfirmed_bests: HashMap<String, Score>,
+    /// How many iterations has been ran
+    pub iter_count: u32,
+    /// When the last leaderboard snapshot was taken
+    pub last_snapshot: Option<Instant>,
+    /// When new levels were last discovered
+    pub last_discovery: Option<Instant>,
+}
+
+impl State {
+    /// Loads the levels, connects to the database and reads all confirmed world records
+    pub async fn new(client: Client, backend: Box<dyn LeaderboardBackend>) -> Result<State> {
+        let mut conn = setup().await;
+        create_tables(&mut conn).await;
+
+        let levels = load_levels(&mut conn).await?;
+
+        Ok(State::from_parts(client, backend, conn, levels).await)
+    }
+
+    /// Creates the state from an already set up connection, backend and levels
+    ///
+    /// Reads all confirmed world records for the active levels from the connection
+    pub async fn from_parts(
+        client: Client,
+        backend: Box<dyn LeaderboardBackend>,
+        mut conn: SqliteConnection,
+        levels: Levels,
+    ) -> State {
+        let confirmed_wrs = get_all(&mut conn, &levels.active_ids()).await;
+
+        State {
+            client,
+            backend,
+            conn,
+            levels,
+            confirmed_wrs,
+            unconfirmed_bests: HashMap::new(),
+            iter_count: 0,
+            last_snapshot: None,
+            last_discovery: None,
+        }
+    }
+}
+
+/// Seeds every level without a saved world record with the current backend world record
+///
+/// Nothing gets announced, so a fresh database doesn't post every level as a new world record
+pub async fn seed_missing_wrs(state: &mut State) {
+    let missing: Vec<String> = state
+        .levels
+        .active_ids()
+        .into_iter()
+        .filter(|level| !state.confirmed_wrs.contains_key(&format!("SP_{}", level)))
+        .collect();
+
+    if missing.is_empty() {
+        return;
+    }
+
+    println!(
+        "- {} {} level(s) without a saved world record",
+        "Seeding".yellow().bold(),
+        missing.len()
+    );
+
+    let mut no_scores: usize = 0;
+    for level in missing {
+        match state.backend.fetch_wr(&level).await {
+            Ok(score) => {
+                seed_wr(state, score).await;
+            }
+            Err(err)
+                if err
+                    .downcast_ref::<ParseError>()
+                    .is_some_and(ParseError::is_empty) =>
+            {
+                no_scores += 1
+            }
+            Err(err) => println!(
+                "{} {}: {:#}",
+                "Failed to seed WR for".red().bold(),
+                level,
+                err
+            ),
+        }
+    }
+
+    if no_scores > 0 {
+        println!(
+            "- {} level(s) have no scores on the backend yet",
+            no_scores.to_string().yellow()
+        );
+    }
+}
+
+/// Saves a score as the confirmed world record for a level without announcing it
+///
+/// An already saved record is reused, held and rejected records are never confirmed here
+///
+/// Returns every error that happened along the way
+async fn seed_wr(state: &mut State, mut score: Score) -> Vec<String> {
+    let mut errors: Vec<String> = vec![];
+
+    score.record_id = match find_record_by_time(&mut state.conn, &score).await {
+        Ok(Some((record_id, RecordStatus::Confirmed))) => Some(record_id),
+        // A tie, or a removed record that is back on the backend, becomes the world record
+        Ok(Some((record_id, RecordStatus::Tied | RecordStatus::Removed))) => {
+            if let Err(err) =
+                set_record_status(&mut state.conn, record_id, RecordStatus::Confirmed).await
+            {
+                println!("{}: {}", "Failed to confirm record".red().bold(), err);
+                errors.push(format!("Failed to confirm record: {}", err));
+            }
+            Some(record_id)
+        }
+        // Left to the moderators
+        Ok(Some((_, status @ (RecordStatus::Pending | RecordStatus::Rejected)))) => {
+            let seen = state
+                .unconfirmed_bests
+                .get(&score.map_id)
+                .is_some_and(|best| best.time == score.time && best.user_id == score.user_id);
+            if seen {
+                return errors;
+            }
+
+            println!(
+                "{}: {} ({}, {}, {})",
+                "Not Seeding Unconfirmed Record For".yellow(),
+                score.map_id,
+                score.time,
+                score.username,
+                status.as_str()
+            );
+            state.unconfirmed_bests.insert(score.map_id.clone(), score);
+            return errors;
+        }
+        Ok(None) => {
+            let (record_id, new_errors) =
+                new_wr(&*state.backend, &mut state.conn, score.clone(), &[]).await;
+            errors.extend(new_errors);
+            record_id
+        }
+        Err(err) => {
+            println!("{}: {}", "Failed to look up record".red().bold(), err);
+            errors.push(format!("Failed to look up record: {}", err));
+            return errors;
+        }
+    };
+
+    println!(
+        "{}: {} ({}, {})",
+        "Seeded World Record For".green(),
+        score.map_id,
+        score.time,
+        score.username
+    );
+
+    state.unconfirmed_bests.remove(&score.map_id);
+    state.confirmed_wrs.insert(score.map_id.clone(), score);
+
+    errors
+}
+
+/// The result of a single WR checking iteration
+#[derive(Debug, Default)]
+pub struct IterationReport {
+    /// All new world records found, as `(new, previous)`
+    pub new_wrs: Vec<(Score, Score)>,
+    /// The new weekly challenge, if one started since the last check
+    pub new_weekly: Option<Weekly>,
+    /// Every level whose world record could not be fetched, as `(level id, reason)`
+    pub failed_levels: Vec<(String, String)>,
+    /// New world records that were flagged as suspicious and held for review
+    pub held_for_review: Vec<Score>,
+    /// Level ids discovered from the backend this iteration
+    pub new_levels: Vec<String>,
+    /// World records that were tied by a different user, as `(tie, holder)`
+    pub ties: Vec<(Score, Score)>,
+    /// World records that were removed from the backend, as `(removed, new best)`
+    pub rollbacks: Vec<(Score, Score)>,
+    /// How many webhook messages were successfully sent
+    pub webhooks_sent: usize,
+    /// How many levels got a leaderboard snapshot
+    pub snapshots_taken: usize,
+    /// Every error that happened during the iteration
+    pub errors: Vec<String>,
+}
+
+/// Runs a single WR checking iteration
+///
+/// Fetches new world records and weekly challenges, saves them and sends out webhooks
+pub async fn run_iteration(state: &mut State) -> IterationReport {
+    let start = Instant::now();
+    let mut report = IterationReport::default();
+
+    check_new_levels(state, &mut report).await;
+
+    let batch_size = config::SETTINGS.read().unwrap().parse.limits.batch_size;
+    let wrs = get_wrs(
+        &*state.backend,
+        &state.levels.active_ids(),
+        &state.confirmed_wrs,
+        batch_size,
+    )
+    .await;
+    for (level, err) in wrs.failed {
+        let parse_error = err.downcast_ref::<ParseError>();
+
+        // Levels without any scores yet are skipped quietly, everything else is an error
+        if parse_error.is_some_and(ParseError::is_empty) {
+            println!("{} {}: {:#}", "Skipping WR for".yellow(), level, err);
+        } else {
+            println!(
+                "{} {}: {:#}",
+                "Failed to get WR for".red().bold(),
+                level,
+                err
+            );
+            report
+                .errors
+                .push(format!("Failed to get WR for {}: {:#}", level, err));
+        }
+
+        report.failed_levels.push((level, format!("{:#}", err)));
+    }
+
+    check_wrs(state, wrs.scores, &mut report).await;
+
+    check_weekly(state, &mut report).await;
+    check_snapshots(state, &mut report).await;
+
+    println!(
+        "{} {:0>3} - {}",
+        format!("[{:0<5}s]", start.elapsed().as_millis() as f64 / 1000.0).bright_black(),
+        state.iter_count,
+        "Finished WR Checking Iteration".green().bold()
+    );
+    state.iter_count += 1;
+
+    report
+}
+
+async fn check_wrs(state: &mut State, new_scores: Vec<Score>, report: &mut IterationReport) {
+    let review = config::SETTINGS.read().unwrap().review.clone();
+
+    for mut score in new_scores {
+        let confirmed = match state.confirmed_wrs.get(&score.map_id) {
+            Some(score) => score.clone(),
+            // A level added since startup, nothing to compare against yet
+            None => {
+                let errors = seed_wr(state, score).await;
+                report.errors.extend(errors);
                 continue;
             }
         };
 
-        let mut new_wrs: Vec<(Score, Score)> = vec![];
-
-        for score in new_scores {
-            let confirmed = match confirmed_wrs.get(&score.map_id) {
-                Some(score) => score,
-                None => {
-                    println!(
-                        "{}: {}",
-                        "Failed to get confirmed wr for".red().bold(),
-                        score.map_id
-                    );
-                    continue;
-                }
-            };
+        if score.time > confirmed.time {
+            check_rollback(state, score, confirmed, report).await;
+            continue;
+        }
+
+        if score.time == confirmed.time {
+            check_tie(state, score, confirmed, report).await;
+            continue;
+        }
 
-            if score.time >= confirmed.time {
+        // Already seen, either waiting for review or handled by a moderator.
+        // Matched on the time alone, resubmitting the same time updates the score on the backend
+        match find_record_by_time(&mut state.conn, &score).await {
+            Ok(Some((
+                _,
+                RecordStatus::Pending
+                | RecordStatus::Rejected
+                | RecordStatus::Removed
+                | RecordStatus::Tied,
+            ))) => continue,
+            // Approved by a moderator, `review approve` already announced it
+            Ok(Some((record_id, RecordStatus::Confirmed))) => {
+                score.record_id = Some(record_id);
+                state.confirmed_wrs.insert(score.map_id.clone(), score);
                 continue;
             }
+            Ok(None) => (),
+            Err(err) => {
+                println!("{}: {}", "Failed to look up record".red().bold(), err);
+                report
+                    .errors
+                    .push(format!("Failed to look up record: {}", err));
+                continue;
+            }
+        }
 
-            // New World record
-            new_wrs.push((score.clone(), confirmed.clone()));
-            //Update confirmed_wrs
-            match confirmed_wrs.get_mut(&score.map_id) {
-                Some(c_score) => {
-                    *c_score = score.to_owned();
-                    println!(
-                        "{}: {} ({}, {}, {})",
-                        "New World Record For".green().bold(),
-                        score.map_id,
-                        score.time,
-                        score.username,
-                        score.platform
-                    );
-
-                    new_wr(&client, &mut conn, score).await;
-                }
-                None => {
-                    println!("{}", "Failed to update confirmed wrs".red().bold());
-                    continue;
-                }
-            };
+        let reasons = match &review {
+            Some(review) => check_plausibility(&score, &confirmed, review),
+            None => vec![],
+        };
+
+        if !reasons.is_empty() {
+            println!(
+                "{}: {} ({}, {}) {}",
+                "Holding Suspicious World Record For".yellow().bold(),
+                score.map_id,
+                score.time,
+                score.username,
+                reasons.join(", ")
+            );
+
+            let (record_id, errors) =
+                new_wr(&*state.backend, &mut state.conn, score.clone(), &reasons).await;
+            report.errors.extend(errors);
+
+            score.record_id = record_id;
+            let level = state
+                .levels
+                .get_or_new(score.map_id.trim_start_matches("SP_"));
+            report.webhooks_sent +=
+                send_review_webhook(&state.client, &score, &confirmed, &level, &reasons)
+                    .await
+                    .len();
+            report.held_for_review.push(score);
+            continue;
         }
 
-        send_webhooks(&client, new_wrs, &level_titles).await;
+        // New World record
+        println!(
+            "{}: {} ({}, {}, {})",
+            "New World Record For".green().bold(),
+            score.map_id,
+            score.time,
+            score.username,
+            score.platform
+        );
+
+        let (record_id, errors) =
+            new_wr(&*state.backend, &mut state.conn, score.clone(), &[]).await;
+        report.errors.extend(errors);
 
-        // Weekly part, refactor into different function
-        let new_weekly = check(&mut conn, &client).await;
-        if let Some(weekly_data) = new_weekly.1 {
-            let prev_scores =
-                fetch(&client, &WeekState::Previous, &weekly_data.score_buckets).await;
+        score.record_id = record_id;
+        report.new_wrs.push((score.clone(), confirmed));
+        state.confirmed_wrs.insert(score.map_id.clone(), score);
+    }
 
-            if let Ok(scores) = prev_scores {
-                send_weekly_embed(&client, &weekly_data, &scores).await;
+    report.webhooks_sent += send_webhooks(&state.client, &report.new_wrs, &state.levels)
+        .await
+        .len();
+}
 
-                println!(
-                    "{} [{}]",
-                    "New Weekly Challenge Posted!".green().bold(),
-                    &weekly_data.score_buckets.current.get_name(NameLang::En)
-                );
-            } else {
-                println!("{}", "No Scores for previous weekly".red().bold());
+/// Handles the backend's best time being the same as the confirmed world record
+///
+/// A different user tying it is saved as a tie, and optionally announced.
+/// The same user resubmitting the same time is ignored
+async fn check_tie(
+    state: &mut State,
+    mut score: Score,
+    confirmed: Score,
+    report: &mut IterationReport,
+) {
+    if score.user_id == confirmed.user_id {
+        return;
+    }
+
+    // Matched on the time alone, resubmitting the same time updates the score on the backend
+    match find_record_by_time(&mut state.conn, &score).await {
+        Ok(Some(_)) => return,
+        Ok(None) => (),
+        Err(err) => {
+            println!("{}: {}", "Failed to look up record".red().bold(), err);
+            report
+                .errors
+                .push(format!("Failed to look up record: {}", err));
+            return;
+        }
+    }
+
+    println!(
+        "{}: {} ({}, {} tied {})",
+        "World Record Tied For".green().bold(),
+        score.map_id,
+        score.time,
+        score.username,
+        confirmed.username
+    );
+
+    match insert_record(&mut state.conn, &score, RecordStatus::Tied, &[]).await {
+        Ok(record_id) => score.record_id = Some(record_id),
+        Err(err) => {
+            println!("{}: {}", "Failed to save tie into db".red().bold(), err);
+            report
+                .errors
+                .push(format!("Failed to save tie into db: {}", err));
+        }
+    }
+
+    let announce = config::SETTINGS.read().unwrap().discord.announce_ties;
+    if announce {
+        let level = state
+            .levels
+            .get_or_new(score.map_id.trim_start_matches("SP_"));
+        report.webhooks_sent += send_tie_webhook(&state.client, &score, &confirmed, &level)
+            .await
+            .len();
+    }
+
+    report.ties.push((score, confirmed));
+}
+
+/// Handles the backend's best time being slower than the confirmed world record
+///
+/// Which means the world record was removed, so every faster record is marked as removed,
+/// and the backend's best becomes the confirmed world record
+async fn check_rollback(
+    state: &mut State,
+    mut score: Score,
+    confirmed: Score,
+    report: &mut IterationReport,
+) {
+    let level = score.map_id.trim_start_matches("SP_").to_owned();
+
+    let removed = match remove_records_faster_than(&mut state.conn, &level, score.time).await {
+        Ok(removed) => removed,
+        Err(err) => {
+            println!("{}: {}", "Failed to remove world records".red().bold(), err);
+            report
+                .errors
+                .push(format!("Failed to remove world records: {}", err));
+            return;
+        }
+    };
+
+    println!(
+        "{}: {} ({} > {}, removed {} record(s))",
+        "World Record Rolled Back For".yellow().bold(),
+        score.map_id,
+        score.time,
+        confirmed.time,
+        removed.len()
+    );
+
+    // The new best might have been saved before the removed record beat it
+    score.record_id = match find_record_by_time(&mut state.conn, &score).await {
+        Ok(Some((record_id, RecordStatus::Confirmed))) => Some(record_id),
+        // A tie, or a removed record that is back on the backend, becomes the world record
+        Ok(Some((record_id, RecordStatus::Tied | RecordStatus::Removed))) => {
+            if let Err(err) =
+                set_record_status(&mut state.conn, record_id, RecordStatus::Confirmed).await
+            {
+                println!("{}: {}", "Failed to confirm record".red().bold(), err);
+                report
+                    .errors
+                    .push(format!("Failed to confirm record: {}", err));
             }
+            Some(record_id)
+        }
+        // Left to the moderators, the level has no confirmed world record until one is approved.
+        // Not announced, and seeding remembers it so the rollback isn't handled every iteration
+        Ok(Some((_, RecordStatus::Pending | RecordStatus::Rejected))) => {
+            state.confirmed_wrs.remove(&score.map_id);
+            state
+                .unconfirmed_bests
+                .insert(score.map_id.clone(), score.clone());
+            report.rollbacks.push((confirmed, score));
+            return;
+        }
+        Ok(None) => {
+            let (record_id, errors) =
+                new_wr(&*state.backend, &mut state.conn, score.clone(), &[]).await;
+            report.errors.extend(errors);
+            record_id
+        }
+        Err(err) => {
+            report
+                .errors
+                .push(format!("Failed to look up record: {}", err));
+            None
+        }
+    };
 
-            // always upsert weekly even if no previous scores
-            db::upsert_weekly_end(&mut conn, weekly_data.score_buckets.current.end_date).await;
+    let announce = config::SETTINGS.read().unwrap().discord.announce_rollbacks;
+    if announce {
+        let level = state.levels.get_or_new(&level);
+        report.webhooks_sent += send_rollback_webhook(&state.client, &confirmed, &score, &level)
+            .await
+            .len();
+    }
 
-            let latest_scores = db::get_latest_world_records(
-                &mut conn,
-                chrono::Duration::days(7),
-                &level_ids,
-                &level_titles,
-            )
+    report.rollbacks.push((confirmed, score.clone()));
+    state.confirmed_wrs.insert(score.map_id.clone(), score);
+}
+
+async fn check_weekly(state: &mut State, report: &mut IterationReport) {
+    let new_weekly = check(&mut state.conn, &*state.backend).await;
+    let weekly_data = match new_weekly.1 {
+        Some(weekly_data) => weekly_data,
+        None => return,
+    };
+
+    let prev_scores = fetch(
+        &*state.backend,
+        &WeekState::Previous,
+        &weekly_data.score_buckets,
+    )
+    .await;
+
+    let prev_scores = match prev_scores {
+        Ok(scores) => {
+            report.webhooks_sent += send_weekly_embed(&state.client, &weekly_data, &scores).await;
+
+            println!(
+                "{} [{}]",
+                "New Weekly Challenge Posted!".green().bold(),
+                &weekly_data.score_buckets.current.get_name(NameLang::En)
+            );
+
+            Some(scores)
+        }
+        Err(err) => {
+            println!("{}", "No Scores for previous weekly".red().bold());
+            report
+                .errors
+                .push(format!("No Scores for previous weekly: {:#}", err));
+
+            None
+        }
+    };
+
+    // Skipped on a failed fetch, so the saved winners aren't overwritten with nothing
+    if let Some(prev_scores) = prev_scores {
+        let history = WeeklyHistory::new(&weekly_data.score_buckets.previous, prev_scores);
+        if let Err(err) = db::upsert_weekly_history(&mut state.conn, &history).await {
+            println!("{}: {}", "Failed to save weekly history".red().bold(), err);
+            report
+                .errors
+                .push(format!("Failed to save weekly history: {}", err));
+        }
+    }
+
+    // always upsert weekly even if no previous scores
+    db::upsert_weekly_end(&mut state.conn, weekly_data.score_buckets.current.end_date).await;
+
+    let latest_scores =
+        db::get_latest_world_records(&mut state.conn, chrono::Duration::days(7), &state.levels)
             .await;
-            if let Some(scores) = latest_scores {
-                miu::weekly_recap(
-                    &client,
-                    scores,
-                    (
-                        weekly_data.score_buckets.previous.start_date,
-                        weekly_data.score_buckets.previous.end_date,
-                    ),
-                )
-                .await;
+    if let Some(scores) = latest_scores {
+        report.webhooks_sent += miu::weekly_recap(
+            &state.client,
+            scores,
+            (
+                weekly_data.score_buckets.previous.start_date,
+                weekly_data.score_buckets.previous.end_date,
+            ),
+        )
+        .await
+        .len();
+    }
+
+    report.new_weekly = Some(weekly_data);
+}
+
+/// Looks for levels on the backend that aren't known yet
+///
+/// New levels are saved, seeded with their current world record and optionally announced
+async fn check_new_levels(state: &mut State, report: &mut IterationReport) {
+    let (interval, announce) = match &config::SETTINGS.read().unwrap().discovery {
+        Some(discovery) => (
+            Duration::from_secs(discovery.interval_seconds),
+            discovery.announce,
+        ),
+        None => return,
+    };
+
+    if let Some(last) = state.last_discovery {
+        if last.elapsed() < interval {
+            return;
+        }
+    }
+    state.last_discovery = Some(Instant::now());
+
+    let levels = match state.backend.fetch_level_ids().await {
+        Ok(levels) => levels,
+        Err(err) => {
+            println!("{}: {:#}", "Failed to discover levels".red().bold(), err);
+            report
+                .errors
+                .push(format!("Failed to discover levels: {:#}", err));
+            return;
+        }
+    };
+
+    for level in discover_levels(state, levels).await {
+        println!("{}: {}", "New Level Discovered".green().bold(), level.title);
+
+        let wr = match state.backend.fetch_wr(&level.id).await {
+            Ok(score) => {
+                report.errors.extend(seed_wr(state, score.clone()).await);
+                Some(score)
+            }
+            // Seeded by the next iteration once it has a score
+            Err(err)
+                if err
+                    .downcast_ref::<ParseError>()
+                    .is_some_and(ParseError::is_empty) =>
+            {
+                None
+            }
+            Err(err) => {
+                println!(
+                    "{} {}: {:#}",
+                    "Failed to get WR for new level".red().bold(),
+                    level.id,
+                    err
+                );
+                report.errors.push(format!(
+                    "Failed to get WR for new level {}: {:#}",
+                    level.id, err
+                ));
+                None
             }
+        };
+
+        if announce {
+            report.webhooks_sent += send_new_level_webhook(&state.client, &level, wr.as_ref())
+                .await
+                .len();
         }
 
-        if let Some(kuma_url) = &config::SETTINGS.read().unwrap().kuma_push_url {
-            match &client.get(kuma_url).send().await {
-                Ok(_) => println!("{}", "Successfully sent a kuma push".green()),
-                Err(err) => println!("{}: {}", "Failed to send a kuma push".red(), err),
+        report.new_levels.push(level.id);
+    }
+}
+
+/// Saves every level id that isn't known yet and adds it to `state.levels`
+///
+/// Returns the new levels
+pub async fn discover_levels(state: &mut State, levels: Vec<String>) -> Vec<Level> {
+    let mut new_levels: Vec<Level> = vec![];
+
+    for level in levels {
+        if state.levels.contains(&level) {
+            continue;
+        }
+
+        if let Err(err) = insert_level(&mut state.conn, &level).await {
+            println!("{}: {}", "Failed to save new level".red().bold(), err);
+            continue;
+        }
+
+        let level = Level::new(&level);
+        state.levels.push(level.clone());
+        new_levels.push(level);
+    }
+
+    new_levels
+}
+
+async fn check_snapshots(state: &mut State, report: &mut IterationReport) {
+    let (top_n, interval) = match &config::SETTINGS.read().unwrap().snapshot {
+        Some(snapshot) => (
+            snapshot.top_n,
+            Duration::from_secs(snapshot.interval_seconds),
+        ),
+        None => return,
+    };
+
+    if let Some(last) = state.last_snapshot {
+        if last.elapsed() < interval {
+            return;
+        }
+    }
+
+    let (taken, errors) = take_snapshots(
+        &*state.backend,
+        &mut state.conn,
+        &state.levels.active_ids(),
+        top_n,
+    )
+    .await;
+    report.snapshots_taken = taken;
+    report.errors.extend(errors);
+
+    state.last_snapshot = Some(Instant::now());
+}
+
+/// Saves a new world record into the database and downloads its replay
+///
+/// The record is held as pending review if any `review_reasons` are given
+///
+/// Returns the row id of the record, and every error that happened along the way
+async fn new_wr(
+    backend: &dyn LeaderboardBackend,
+    conn: &mut SqliteConnection,
+    score: Score,
+    review_reasons: &[String],
+) -> (Option<i64>, Vec<String>) {
+    let mut errors: Vec<String> = vec![];
+
+    let status = match review_reasons.is_empty() {
+        true => RecordStatus::Confirmed,
+        false => RecordStatus::Pending,
+    };
+
+    let record_id = match insert_record(conn, &score, status, review_reasons).await {
+        Ok(id) => Some(id),
+        Err(err) => {
+            println!("{}: {}", "Failed to update score into db".red().bold(), err);
+            errors.push(format!("Failed to update score into db: {}", err));
+            None
+        }
+    };
+
+    match download_replay(backend, &score).await {
+        Ok(file) => {
+            println!(
+                "{}: [{}] {}, {}",
+                "Downloaded Replay For".green(),
+                score.map_id,
+                score.username,
+                score.time
+            );
+
+            if let Some(record_id) = record_id {
+                if let Err(err) = insert_replay(conn, record_id, &file).await {
+                    println!("{}: {}", "Failed to index replay".red().bold(), err);
+                    errors.push(format!("Failed to index replay: {}", err));
+                }
             }
         }
+        Err(err) => {
+            println!("Failed during replay handle: {:#}", err);
+            errors.push(format!("Failed during replay handle: {:#}", err));
+        }
+    };
 
-        println!(
-            "{} {:0>3} - {}",
-            format!("[{:0<5}s]", start.elapsed().as_millis() as f64 / 1000.0).bright_black(),
-            iter_count,
-            "Finished WR Checking Iteration".green().bold()
-        );
-        iter_count += 1;
+    (record_id, errors)
+}
+
+#[tokio::test]
+async fn test_seed_missing_wrs() {
+    use crate::{backend::fixture::FixtureBackend, test_util::get_fake_score};
+    use sqlx::Connection;
+
+    let backend = FixtureBackend::default();
+    let mut score = get_fake_score(5.0..6.0);
+    score.map_id = String::from("SP_seeded");
+    backend.set_wr(score.clone());
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let levels = Levels::parse(
+        r#"{ "version": 1, "levels": [
+            { "id": "seeded", "title": "Seeded" },
+            { "id": "no_scores", "title": "No Scores" }
+        ] }"#,
+    )
+    .unwrap();
+    let mut state = State::from_parts(Client::new(), Box::new(backend), conn, levels).await;
+    seed_missing_wrs(&mut state).await;
+
+    assert_eq!(1, state.confirmed_wrs.len());
+    assert!(state.confirmed_wrs["SP_seeded"].record_id.is_some());
+
+    let saved = get_wr(&mut state.conn, "seeded").await.unwrap().unwrap();
+    assert_eq!(score.time, saved.time);
+}
+
+#[tokio::test]
+async fn test_discover_levels() {
+    use crate::backend::fixture::FixtureBackend;
+    use sqlx::Connection;
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
 
-        sleep(sleep_wait);
+    let levels =
+        Levels::parse(r#"{ "version": 1, "levels": [{ "id": "known", "title": "Known" }] }"#)
+            .unwrap();
+    let mut state = State::from_parts(
+        Client::new(),
+        Box::new(FixtureBackend::default()),
+        conn,
+        levels,
+    )
+    .await;
+
+    let levels = vec![String::from("known"), String::from("new")];
+    let new_levels = discover_levels(&mut state, levels.clone()).await;
+    assert_eq!(vec![Level::new("new")], new_levels);
+    assert!(discover_levels(&mut state, levels).await.is_empty());
+
+    assert_eq!(vec!["known", "new"], state.levels.ids());
+    assert_eq!(vec!["new"], get_levels(&mut state.conn).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_run_iteration() {
+    use crate::{
+        backend::fixture::FixtureBackend,
+        test_util::{get_fake_score, init_test_settings},
+    };
+    use sqlx::Connection;
+
+    init_test_settings();
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let mut old = get_fake_score(6.0..7.0);
+    old.map_id = String::from("SP_beaten");
+    insert_record(&mut conn, &old, RecordStatus::Confirmed, &[])
+        .await
+        .unwrap();
+
+    let backend = FixtureBackend::default();
+    let mut new = get_fake_score(5.0..6.0);
+    new.map_id = String::from("SP_beaten");
+    backend.set_wr(new.clone());
+
+    let levels = Levels::parse(
+        r#"{ "version": 1, "levels": [
+            { "id": "beaten", "title": "Beaten" },
+            { "id": "no_scores", "title": "No Scores" }
+        ] }"#,
+    )
+    .unwrap();
+    let mut state = State::from_parts(Client::new(), Box::new(backend), conn, levels).await;
+
+    let report = run_iteration(&mut state).await;
+
+    assert_eq!(1, report.new_wrs.len());
+    assert_eq!(new.time, report.new_wrs[0].0.time);
+    assert_eq!(old.time, report.new_wrs[0].1.time);
+    assert!(report.new_wrs[0].0.record_id.is_some());
+    assert_eq!(new.time, state.confirmed_wrs["SP_beaten"].time);
+
+    // Levels without scores are reported as failed, but aren't an error
+    assert_eq!(1, report.failed_levels.len());
+    assert_eq!("no_scores", report.failed_levels[0].0);
+
+    // The fixture has no replay for the new world record
+    assert_eq!(1, report.errors.len());
+    assert!(report.errors[0].starts_with("Failed during replay handle"));
+    assert_eq!(1, state.iter_count);
+}
+
+#[tokio::test]
+async fn test_check_weekly_keeps_history() {
+    use crate::{
+        backend::fixture::FixtureBackend,
+        miu::weekly_data::{Challenge, ScoreBucket},
+        test_util::{get_fake_score, init_test_settings},
+    };
+    use chrono::Utc;
+    use sqlx::Connection;
+
+    init_test_settings();
+
+    let challenge = |id: &str, days_ago: i64| Challenge {
+        chapter_set: String::from("chapter"),
+        challenge_id: String::from(id),
+        levels: vec![],
+        name: HashMap::from([(String::from("en"), String::from(id))]),
+        start_date: Utc::now() - chrono::Duration::days(days_ago + 7),
+        end_date: Utc::now() - chrono::Duration::days(days_ago),
+    };
+    let weekly = Weekly {
+        object_id: String::from("object"),
+        level_id: String::from("CHALLENGE_DATA"),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        score_buckets: ScoreBucket {
+            current: challenge("current", -7),
+            previous: challenge("previous", 0),
+            sheet_id: 0,
+            cur_id: 0,
+            level: String::new(),
+        },
+    };
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let history = WeeklyHistory::new(
+        &weekly.score_buckets.previous,
+        vec![get_fake_score(5.0..6.0)],
+    );
+    upsert_weekly_history(&mut conn, &history).await.unwrap();
+
+    // The fixture has no scores for the previous challenge, so fetching them fails
+    let backend = FixtureBackend::default();
+    backend.set_weekly(weekly);
+
+    let mut state =
+        State::from_parts(Client::new(), Box::new(backend), conn, Levels::default()).await;
+    let mut report = IterationReport::default();
+    check_weekly(&mut state, &mut report).await;
+
+    assert!(report.new_weekly.is_some());
+    assert_eq!(1, report.errors.len());
+
+    let saved = get_weekly_history_by_id(&mut state.conn, "previous")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(history.scores[0].time, saved.scores[0].time);
+}
+
+#[tokio::test]
+async fn test_rollback_statuses() {
+    use crate::{
+        backend::fixture::FixtureBackend,
+        test_util::{get_fake_score, init_test_settings},
+    };
+    use sqlx::Connection;
+
+    init_test_settings();
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+    let backend = FixtureBackend::default();
+
+    // Every level has a 5.0 world record that is gone from the backend,
+    // leaving a slower score with a different status as the backend best
+    let mut best: HashMap<&str, Score> = HashMap::new();
+    for (level, status) in [
+        ("tied", Some(RecordStatus::Tied)),
+        ("pending", Some(RecordStatus::Pending)),
+        ("rejected", Some(RecordStatus::Rejected)),
+        ("unsaved", None),
+    ] {
+        let mut removed = get_fake_score(5.0..5.0001);
+        removed.map_id = format!("SP_{}", level);
+        insert_record(&mut conn, &removed, RecordStatus::Confirmed, &[])
+            .await
+            .unwrap();
+
+        let mut score = get_fake_score(6.0..7.0);
+        score.map_id = format!("SP_{}", level);
+        if let Some(status) = status {
+            insert_record(&mut conn, &score, status, &[]).await.unwrap();
+        }
+
+        backend.set_wr(score.clone());
+        best.insert(level, score);
+    }
+
+    let levels = Levels::parse(
+        r#"{ "version": 1, "levels": [
+            { "id": "tied", "title": "Tied" },
+            { "id": "pending", "title": "Pending" },
+            { "id": "rejected", "title": "Rejected" },
+            { "id": "unsaved", "title": "Unsaved" }
+        ] }"#,
+    )
+    .unwrap();
+    let mut state = State::from_parts(Client::new(), Box::new(backend), conn, levels).await;
+
+    let report = run_iteration(&mut state).await;
+    assert_eq!(4, report.rollbacks.len());
+
+    for (level, expected) in [
+        ("tied", RecordStatus::Confirmed),
+        ("pending", RecordStatus::Pending),
+        ("rejected", RecordStatus::Rejected),
+        ("unsaved", RecordStatus::Confirmed),
+    ] {
+        let (_, status) = find_record(&mut state.conn, &best[level])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, status, "{}", level);
+    }
+
+    // Only the unsaved score is inserted, rejected and pending records aren't confirmed
+    assert!(get_wr(&mut state.conn, "pending").await.unwrap().is_none());
+    assert!(get_wr(&mut state.conn, "rejected").await.unwrap().is_none());
+    for level in ["tied", "unsaved"] {
+        let wr = get_wr(&mut state.conn, level).await.unwrap().unwrap();
+        assert_eq!(best[level].time, wr.time);
     }
+
+    // The same rollback isn't handled again
+    let report = run_iteration(&mut state).await;
+    assert!(report.rollbacks.is_empty());
+    assert!(report.errors.is_empty());
+    assert!(!state.confirmed_wrs.contains_key("SP_pending"));
+    assert!(!state.confirmed_wrs.contains_key("SP_rejected"));
+
+    // Nor confirmed when seeding after a restart
+    let mut state = State::from_parts(Client::new(), state.backend, state.conn, state.levels).await;
+    seed_missing_wrs(&mut state).await;
+    assert!(get_wr(&mut state.conn, "pending").await.unwrap().is_none());
+    assert!(get_wr(&mut state.conn, "rejected").await.unwrap().is_none());
+    assert!(state.unconfirmed_bests.contains_key("SP_rejected"));
 }
 
-async fn new_wr(client: &Client, conn: &mut SqliteConnection, score: Score) {
-    match update_level(conn, &score).await {
-        Err(err) => println!("{}: {}", "Failed to update score into db".red().bold(), err),
-        _ => (),
+#[tokio::test]
+async fn test_tie_resubmission() {
+    use crate::{
+        backend::fixture::FixtureBackend,
+        test_util::{get_fake_score, init_test_settings},
     };
+    use sqlx::Connection;
 
-    match download_replay(client, &score).await {
-        Ok(_) => println!(
-            "{}: [{}] {}, {}",
-            "Downloaded Replay For".green(),
-            score.map_id,
-            score.username,
-            score.time
-        ),
-        Err(err) => println!("Failed during replay handle: {}", err),
+    init_test_settings();
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let mut holder = get_fake_score(5.0..6.0);
+    holder.map_id = String::from("SP_tied");
+    holder.user_id = String::from("UserId1");
+    insert_record(&mut conn, &holder, RecordStatus::Confirmed, &[])
+        .await
+        .unwrap();
+
+    let levels =
+        Levels::parse(r#"{ "version": 1, "levels": [{ "id": "tied", "title": "Tied" }] }"#)
+            .unwrap();
+    let mut state = State::from_parts(
+        Client::new(),
+        Box::new(FixtureBackend::default()),
+        conn,
+        levels,
+    )
+    .await;
+
+    let mut tie = holder.clone();
+    tie.user_id = String::from("UserId2");
+    tie.updated_at = holder.updated_at + chrono::Duration::minutes(1);
+
+    let mut report = IterationReport::default();
+    check_wrs(&mut state, vec![tie.clone()], &mut report).await;
+    assert_eq!(1, report.ties.len());
+
+    // Submitting the same time again only changes when the score was updated
+    tie.updated_at += chrono::Duration::minutes(1);
+
+    let mut report = IterationReport::default();
+    check_wrs(&mut state, vec![tie], &mut report).await;
+    assert!(report.ties.is_empty());
+    assert!(report.errors.is_empty());
+}
+
+#[tokio::test]
+async fn test_check_new_levels() {
+    use crate::{
+        backend::fixture::FixtureBackend,
+        miu::{
+            score::Replay,
+            weekly_data::{Challenge, Weekly},
+        },
+        test_util::{get_fake_score, init_test_settings},
     };
+    use async_trait::async_trait;
+    use sqlx::Connection;
+
+    /// Discovers one extra level that fails to fetch
+    struct BrokenLevel(FixtureBackend);
+
+    #[async_trait]
+    impl LeaderboardBackend for BrokenLevel {
+        async fn fetch_wr(&self, level: &str) -> Result<Score> {
+            match level {
+                "broken" => Err(anyhow!("Connection reset")),
+                level => self.0.fetch_wr(level).await,
+            }
+        }
+        async fn fetch_level_ids(&self) -> Result<Vec<String>> {
+            let mut levels = self.0.fetch_level_ids().await?;
+            levels.push(String::from("broken"));
+            Ok(levels)
+        }
+        async fn fetch_top(&self, level: &str, limit: u32) -> Result<Vec<Score>> {
+            self.0.fetch_top(level, limit).await
+        }
+        async fn fetch_weekly(&self) -> Result<Weekly> {
+            self.0.fetch_weekly().await
+        }
+        async fn fetch_weekly_scores(&self, challenge: &Challenge) -> Result<Vec<Score>> {
+            self.0.fetch_weekly_scores(challenge).await
+        }
+        async fn download_replay(&self, replay: &Replay) -> Result<Vec<u8>> {
+            self.0.download_replay(replay).await
+        }
+    }
+
+    init_test_settings();
+
+    let backend = FixtureBackend::default();
+    for level in ["known", "new"] {
+        let mut score = get_fake_score(5.0..6.0);
+        score.map_id = format!("SP_{}", level);
+        score.replay = None;
+        backend.set_wr(score);
+    }
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let levels =
+        Levels::parse(r#"{ "version": 1, "levels": [{ "id": "known", "title": "Known" }] }"#)
+            .unwrap();
+    let mut state =
+        State::from_parts(Client::new(), Box::new(BrokenLevel(backend)), conn, levels).await;
+
+    let mut report = IterationReport::default();
+    check_new_levels(&mut state, &mut report).await;
+
+    assert_eq!(vec!["new", "broken"], report.new_levels);
+    assert_eq!(vec!["known", "new", "broken"], state.levels.ids());
+    assert!(state.confirmed_wrs.contains_key("SP_new"));
+    assert!(!state.confirmed_wrs.contains_key("SP_broken"));
+
+    // The seeded WR has no replay, the broken level is reported
+    assert_eq!(2, report.errors.len());
+    assert!(report.errors[1].starts_with("Failed to get WR for new level broken"));
+
+    // Only checked again once the interval has passed
+    let mut report = IterationReport::default();
+    check_new_levels(&mut state, &mut report).await;
+    assert!(report.new_levels.is_empty());
+}
+
+#[tokio::test]
+async fn test_held_resubmission() {
+    use crate::{
+        backend::fixture::FixtureBackend,
+        test_util::{get_fake_score, init_test_settings},
+    };
+    use sqlx::Connection;
+
+    init_test_settings();
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let mut wr = get_fake_score(6.0..7.0);
+    wr.map_id = String::from("SP_held");
+    insert_record(&mut conn, &wr, RecordStatus::Confirmed, &[])
+        .await
+        .unwrap();
+
+    let mut held = get_fake_score(1.0..2.0);
+    held.map_id = String::from("SP_held");
+    insert_record(
+        &mut conn,
+        &held,
+        RecordStatus::Pending,
+        &[String::from("Too fast")],
+    )
+    .await
+    .unwrap();
+
+    let levels =
+        Levels::parse(r#"{ "version": 1, "levels": [{ "id": "held", "title": "Held" }] }"#)
+            .unwrap();
+    let mut state = State::from_parts(
+        Client::new(),
+        Box::new(FixtureBackend::default()),
+        conn,
+        levels,
+    )
+    .await;
+
+    // Submitting the same time again only changes when the score was updated
+    held.updated_at += chrono::Duration::minutes(1);
+
+    let mut report = IterationReport::default();
+    check_wrs(&mut state, vec![held], &mut report).await;
+    assert!(report.new_wrs.is_empty());
+    assert!(report.held_for_review.is_empty());
+    assert_eq!(1, get_pending_reviews(&mut state.conn).await.unwrap().len());
+    assert_eq!(wr.time, state.confirmed_wrs["SP_held"].time);
+}
+
+#[tokio::test]
+async fn test_seed_saved_records() {
+    use crate::{backend::fixture::FixtureBackend, test_util::get_fake_score};
+    use sqlx::Connection;
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+    let backend = FixtureBackend::default();
+
+    // Every level's backend best is already saved with a different status
+    let mut best: HashMap<&str, Score> = HashMap::new();
+    for (level, status) in [
+        ("confirmed", RecordStatus::Confirmed),
+        ("pending", RecordStatus::Pending),
+        ("rejected", RecordStatus::Rejected),
+        ("removed", RecordStatus::Removed),
+        ("tied", RecordStatus::Tied),
+    ] {
+        let mut score = get_fake_score(5.0..6.0);
+        score.map_id = format!("SP_{}", level);
+        insert_record(&mut conn, &score, status, &[]).await.unwrap();
+
+        // Resubmitted since it was saved
+        score.updated_at += chrono::Duration::minutes(1);
+        backend.set_wr(score.clone());
+        best.insert(level, score);
+    }
+
+    let levels = Levels::parse(
+        r#"{ "version": 1, "levels": [
+            { "id": "confirmed", "title": "Confirmed" },
+            { "id": "pending", "title": "Pending" },
+            { "id": "rejected", "title": "Rejected" },
+            { "id": "removed", "title": "Removed" },
+            { "id": "tied", "title": "Tied" }
+        ] }"#,
+    )
+    .unwrap();
+    let mut state = State::from_parts(Client::new(), Box::new(backend), conn, levels).await;
+    state.confirmed_wrs.clear();
+
+    seed_missing_wrs(&mut state).await;
+
+    for (level, expected) in [
+        ("confirmed", Some(RecordStatus::Confirmed)),
+        ("pending", None),
+        ("rejected", None),
+        ("removed", Some(RecordStatus::Confirmed)),
+        ("tied", Some(RecordStatus::Confirmed)),
+    ] {
+        // No duplicate confirmed row is inserted next to the saved record
+        let records = get_user_records(&mut state.conn, &best[level].user_id)
+            .await
+            .unwrap();
+        let confirmed = records
+            .iter()
+            .filter(|record| record.map_id.trim_start_matches("SP_") == level)
+            .count();
+        assert_eq!(expected.is_some() as usize, confirmed, "{}", level);
+
+        let seeded = state.confirmed_wrs.get(&best[level].map_id);
+        assert_eq!(expected.is_some(), seeded.is_some(), "{}", level);
+
+        if let Some(expected) = expected {
+            let (_, status) = find_record_by_time(&mut state.conn, &best[level])
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(expected, status, "{}", level);
+        }
+    }
 }
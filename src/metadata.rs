@@ -1,20 +1,248 @@
-//! Loads metadata like level ids and titles
+//! Loads metadata like level ids, titles and medal times
 
-use std::{collections::HashMap, fs};
+use std::{fmt, fs};
 
-/// Loads and deserializes all level ids
-pub fn load_name_vec() -> Vec<String> {
-    let raw =
-        fs::read_to_string("./miuu_level_ids.json").expect("Failed to load level name vec file");
+use anyhow::{anyhow, Context, Result};
+use chrono::NaiveDate;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
 
-    serde_json::from_str::<Vec<String>>(&raw).expect("Failed to parse level name vec file")
+use crate::db;
+
+/// The level metadata file, read from the working directory
+pub const METADATA_PATH: &str = "./miuu_levels.json";
+
+/// The only metadata file version this build can read
+///
+/// Bumped whenever a change to the file format isn't backwards compatible
+pub const METADATA_VERSION: u32 = 1;
+
+/// Everything known about a single level
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Level {
+    /// The level id, *without the `SP_` prefix*
+    pub id: String,
+    /// The fancy level title
+    pub title: String,
+    /// The chapter or world the level is in
+    pub chapter: Option<String>,
+    /// The difficulty shown in game
+    pub difficulty: Option<String>,
+    /// The par time, in seconds
+    pub par_time: Option<f32>,
+    /// The gold medal time, in seconds
+    pub gold_time: Option<f32>,
+    /// The diamond medal time, in seconds
+    pub diamond_time: Option<f32>,
+    /// When the level was released
+    pub release_date: Option<NaiveDate>,
+    /// Retired levels are kept for history but are no longer checked for new world records
+    #[serde(default)]
+    pub retired: bool,
+    /// An image of the level, used as the embed thumbnail
+    pub thumbnail_url: Option<String>,
 }
 
-/// Loads and deserializes all level id > level title
-pub fn load_name_conversion_map() -> HashMap<String, String> {
-    let raw_map = fs::read_to_string("./miuu_levelid_to_name.json")
-        .expect("Failed to load name conversion map file");
+impl Level {
+    /// Creates a level with only an id, used for levels missing from the metadata file
+    ///
+    /// The id is also used as the title
+    pub fn new(id: &str) -> Self {
+        Level {
+            id: id.to_owned(),
+            title: id.to_owned(),
+            chapter: None,
+            difficulty: None,
+            par_time: None,
+            gold_time: None,
+            diamond_time: None,
+            release_date: None,
+            retired: false,
+            thumbnail_url: None,
+        }
+    }
+
+    /// Gets every medal time the level has, fastest first
+    pub fn get_medal_times(&self) -> Vec<(Medal, f32)> {
+        [
+            (Medal::Diamond, self.diamond_time),
+            (Medal::Gold, self.gold_time),
+            (Medal::Par, self.par_time),
+        ]
+        .into_iter()
+        .filter_map(|(medal, time)| Some((medal, time?)))
+        .collect()
+    }
+
+    /// Gets the best medal a time would earn, if any
+    pub fn get_medal(&self, time: f32) -> Option<Medal> {
+        self.get_medal_times()
+            .into_iter()
+            .find(|(_, medal_time)| time <= *medal_time)
+            .map(|(medal, _)| medal)
+    }
+}
+
+/// A medal earned by beating its time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Medal {
+    /// The fastest medal
+    Diamond,
+    /// The medal between par and diamond
+    Gold,
+    /// Just finishing under the par time
+    Par,
+}
+
+impl fmt::Display for Medal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Medal::Diamond => "Diamond",
+            Medal::Gold => "Gold",
+            Medal::Par => "Par",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// The raw metadata file
+#[derive(Debug, Deserialize)]
+struct LevelMetadata {
+    version: u32,
+    levels: Vec<Level>,
+}
+
+/// Every known level, in the order of the metadata file
+#[derive(Debug, Clone, Default)]
+pub struct Levels {
+    levels: Vec<Level>,
+}
+
+impl Levels {
+    /// Parses a metadata file, fails on an unknown version or duplicate level ids
+    pub fn parse(raw: &str) -> Result<Self> {
+        let metadata: LevelMetadata = serde_json::from_str(raw)?;
+
+        if metadata.version != METADATA_VERSION {
+            return Err(anyhow!(
+                "Unsupported level metadata version {}, expected {}",
+                metadata.version,
+                METADATA_VERSION
+            ));
+        }
+
+        let mut levels = Levels::default();
+        for level in metadata.levels {
+            if levels.contains(&level.id) {
+                return Err(anyhow!("Duplicate level id in metadata: {}", level.id));
+            }
+
+            levels.push(level);
+        }
+
+        Ok(levels)
+    }
+
+    /// Gets a level by id
+    pub fn get(&self, id: &str) -> Option<&Level> {
+        self.levels.iter().find(|level| level.id == id)
+    }
+
+    /// Gets a level by id, or a level with only the id if it isn't known
+    pub fn get_or_new(&self, id: &str) -> Level {
+        self.get(id).cloned().unwrap_or_else(|| Level::new(id))
+    }
+
+    /// Gets the title of a level, or the id if it isn't known
+    pub fn get_title(&self, id: &str) -> String {
+        self.get(id)
+            .map(|level| level.title.clone())
+            .unwrap_or(id.to_owned())
+    }
+
+    /// Returns true if the level id is known
+    pub fn contains(&self, id: &str) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Adds a level to the end
+    pub fn push(&mut self, level: Level) {
+        self.levels.push(level);
+    }
+
+    /// Gets every level id, including retired levels
+    pub fn ids(&self) -> Vec<String> {
+        self.levels.iter().map(|level| level.id.clone()).collect()
+    }
+
+    /// Gets the id of every level that is still checked for new world records
+    pub fn active_ids(&self) -> Vec<String> {
+        self.levels
+            .iter()
+            .filter(|level| !level.retired)
+            .map(|level| level.id.clone())
+            .collect()
+    }
+
+    /// Iterates over every level
+    pub fn iter(&self) -> impl Iterator<Item = &Level> {
+        self.levels.iter()
+    }
+}
+
+/// Loads and parses a level metadata file
+pub fn load_level_file(path: &str) -> Result<Levels> {
+    let raw = fs::read_to_string(path).context("Failed to load level metadata file")?;
+
+    Levels::parse(&raw).context("Failed to parse level metadata file")
+}
+
+/// Loads all levels from `METADATA_PATH`, followed by every level discovered from the backend
+pub async fn load_levels(conn: &mut SqliteConnection) -> Result<Levels> {
+    load_levels_from(conn, METADATA_PATH).await
+}
+
+/// Loads all levels from a metadata file, followed by every level discovered from the backend
+pub async fn load_levels_from(conn: &mut SqliteConnection, path: &str) -> Result<Levels> {
+    let mut levels = load_level_file(path)?;
+
+    match db::get_levels(conn).await {
+        Ok(discovered) => {
+            for level in discovered {
+                if !levels.contains(&level) {
+                    levels.push(Level::new(&level));
+                }
+            }
+        }
+        Err(err) => println!("{}: {}", "Failed to load discovered levels".red(), err),
+    }
+
+    Ok(levels)
+}
+
+#[test]
+fn test_parse_levels() {
+    let raw = r#"{
+        "version": 1,
+        "levels": [
+            { "id": "bunny_slope", "title": "Bunny Slope", "diamond_time": 5.0, "gold_time": 7.5 },
+            { "id": "old_level", "title": "Old Level", "retired": true }
+        ]
+    }"#;
+
+    let levels = Levels::parse(raw).unwrap();
+    assert_eq!(vec!["bunny_slope", "old_level"], levels.ids());
+    assert_eq!(vec!["bunny_slope"], levels.active_ids());
+    assert_eq!("Bunny Slope", levels.get_title("bunny_slope"));
+    assert_eq!("unknown", levels.get_title("unknown"));
+
+    let bunny_slope = levels.get("bunny_slope").unwrap();
+    assert_eq!(Some(Medal::Diamond), bunny_slope.get_medal(4.9));
+    assert_eq!(Some(Medal::Gold), bunny_slope.get_medal(6.0));
+    assert_eq!(None, bunny_slope.get_medal(8.0));
 
-    serde_json::from_str::<HashMap<String, String>>(&raw_map)
-        .expect("Failed to parse name conversion map file")
+    assert!(Levels::parse(&raw.replace("\"version\": 1", "\"version\": 2")).is_err());
+    assert!(Levels::parse(&raw.replace("old_level", "bunny_slope")).is_err());
 }
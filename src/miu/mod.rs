@@ -1,66 +1,174 @@
 //! Functions and things related to pure Marble It Up! fetching
 
 pub mod replay;
+pub mod review;
 pub mod score;
+pub mod snapshot;
 pub mod weekly;
 pub mod weekly_data;
 
 use crate::{
+    backend::LeaderboardBackend,
     discord::{
         embed,
         webhook::{self, WebhookRequest},
     },
     miu::score::{RecapScore, Score},
-    request::make_request,
 };
-use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
-use futures::future::try_join_all;
+use colored::Colorize;
+use futures::future::join_all;
 use reqwest::Client;
 
+/// The result of fetching world records for many levels
+#[derive(Debug, Default)]
+pub struct WrResults {
+    /// The world records of every level that was fetched successfully
+    pub scores: Vec<Score>,
+    /// Every level that failed, as `(level id, error)`
+    ///
+    /// The error can be downcast to a `request::error::ParseError` to see why
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
 /// Gets all world records for all the given levels
-pub async fn get_wrs(client: &Client, levels: &Vec<String>) -> Result<Vec<Score>> {
-    let mut level_futures = Vec::new();
+///
+/// Levels with a confirmed world record are fetched in batches of `batch_size`,
+/// only asking for scores at least as fast as the confirmed one.
+/// Any level missing from a batch, or in a failed batch, is fetched on its own instead.
+/// A `batch_size` of 0 fetches every level on its own
+///
+/// A level failing does not stop the other levels from being fetched
+pub async fn get_wrs(
+    backend: &dyn LeaderboardBackend,
+    levels: &[String],
+    confirmed: &HashMap<String, Score>,
+    batch_size: usize,
+) -> WrResults {
+    let mut results = WrResults::default();
+    let mut remaining: Vec<&String> = vec![];
 
-    for level in levels {
-        level_futures.push(fetch(&client, level));
-    }
+    let (bounds, unbounded): (Vec<_>, Vec<_>) = levels
+        .iter()
+        .map(|level| (level, confirmed.get(&format!("SP_{}", level))))
+        .partition(|(_, wr)| wr.is_some() && batch_size > 0);
+    remaining.extend(unbounded.into_iter().map(|(level, _)| level));
 
-    try_join_all(level_futures).await
-}
+    let bounds: Vec<(String, f32)> = bounds
+        .into_iter()
+        .filter_map(|(level, wr)| Some((level.to_owned(), wr?.time)))
+        .collect();
 
-async fn fetch(client: &Client, level: &str) -> Result<Score> {
-    let level_str = format!(r#"{{"mapID":"SP_{}"}}"#, level);
-    let params = vec![
-        ("limit", "1"),
-        ("order", "time,-updatedAt"),
-        ("where", &level_str),
-    ];
-
-    match make_request(client, params, None, None).await {
-        Ok(mut score) => {
-            if score.is_empty() {
-                return Err(anyhow!("Empty Scores returned from: {}", level));
+    for chunk in bounds.chunks(batch_size.max(1)) {
+        let mut best: HashMap<String, Score> = HashMap::new();
+
+        match backend.fetch_wr_candidates(chunk).await {
+            Ok(candidates) => {
+                for score in candidates {
+                    match best.get(&score.map_id) {
+                        Some(current) if current.time <= score.time => (),
+                        _ => {
+                            best.insert(score.map_id.clone(), score);
+                        }
+                    }
+                }
             }
+            Err(err) => println!(
+                "{}: {}",
+                "Batched WR fetch failed, falling back to single levels".yellow(),
+                err
+            ),
+        }
 
-            Ok(score.remove(0))
+        for (level, _) in chunk {
+            match best.remove(&format!("SP_{}", level)) {
+                Some(score) => results.scores.push(score),
+                None => remaining.push(level),
+            }
         }
-        Err(err) => Err(anyhow!("[{}] Failed to fetch score, {}", level, err)),
     }
+
+    let level_futures = remaining.iter().map(|level| backend.fetch_wr(level));
+    for (level, result) in remaining.iter().zip(join_all(level_futures).await) {
+        match result {
+            Ok(score) => results.scores.push(score),
+            Err(err) => results.failed.push((level.to_string(), err)),
+        }
+    }
+
+    results
 }
 
 /// Sends out a weekly recap
 ///
-/// Constructs an embed and sends it
+/// Constructs an embed and sends it, returns the sent message ids
 pub async fn weekly_recap(
     client: &Client,
     scores: Vec<RecapScore>,
     dates: (DateTime<Utc>, DateTime<Utc>),
-) {
+) -> Vec<String> {
     let embed = embed::get_weekly_recap_embed(scores, dates);
     let request = &WebhookRequest {
         embeds: vec![embed],
     };
 
-    webhook::send_to_all_webhooks(client, request).await;
+    webhook::send_to_all_webhooks(client, request).await
+}
+
+#[tokio::test]
+async fn test_get_wrs_partial_failure() {
+    use crate::{backend::fixture::FixtureBackend, test_util::get_fake_score};
+
+    let backend = FixtureBackend::default();
+    let mut score = get_fake_score(10.0..20.0);
+    score.map_id = String::from("SP_good");
+    backend.set_wr(score);
+
+    let results = get_wrs(
+        &backend,
+        &[String::from("good"), String::from("missing")],
+        &HashMap::new(),
+        0,
+    )
+    .await;
+
+    assert_eq!(1, results.scores.len());
+    assert_eq!("SP_good", results.scores[0].map_id);
+    assert_eq!(
+        vec!["missing"],
+        results.failed.iter().map(|(l, _)| l).collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn test_get_wrs_batched() {
+    use crate::{backend::fixture::FixtureBackend, test_util::get_fake_score};
+
+    let backend = FixtureBackend::default();
+    let mut confirmed: HashMap<String, Score> = HashMap::new();
+
+    for (level, time, confirmed_time) in [("a", 10.0, 12.0), ("b", 20.0, 15.0)] {
+        let mut score = get_fake_score(time..time + 0.1);
+        score.map_id = format!("SP_{}", level);
+        score.time = time;
+        backend.set_wr(score.clone());
+
+        score.time = confirmed_time;
+        confirmed.insert(score.map_id.clone(), score);
+    }
+
+    // "b" is slower than the confirmed time so it is missing from the batch,
+    // and "c" has no confirmed world record, both get fetched on their own
+    let levels = [String::from("a"), String::from("b"), String::from("c")];
+    let results = get_wrs(&backend, &levels, &confirmed, 10).await;
+
+    let mut map_ids: Vec<&str> = results.scores.iter().map(|s| s.map_id.as_str()).collect();
+    map_ids.sort();
+    assert_eq!(vec!["SP_a", "SP_b"], map_ids);
+    assert_eq!(
+        vec!["c"],
+        results.failed.iter().map(|(l, _)| l).collect::<Vec<_>>()
+    );
 }
@@ -3,16 +3,38 @@
 use std::fs;
 
 use anyhow::{anyhow, Result};
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{prelude::FromRow, SqliteConnection};
 
-use crate::{config::SETTINGS, miu::score::Score, request::raw_request};
+use crate::{backend::LeaderboardBackend, db, miu::score::Score};
+
+/// A replay file saved on disk
+#[derive(Debug, Clone, FromRow)]
+pub struct ReplayFile {
+    /// The path to the file on disk
+    pub path: String,
+    /// The size of the file, in bytes
+    pub size: i64,
+    /// The SHA-256 hash of the file, in lowercase hex
+    pub sha256: String,
+    /// The name of the file on the parse backend
+    #[sqlx(rename = "parseName")]
+    pub parse_name: String,
+    /// When the file was downloaded, in Utc
+    #[sqlx(rename = "downloadedAt")]
+    pub downloaded_at: DateTime<Utc>,
+}
 
 /// Downloads a replay and saves it to disk
 ///
 /// Saves them to `./replay/levelid/filecount_username_time.replay`
-pub async fn download_replay(client: &Client, score: &Score) -> Result<()> {
-    let replay_data = match score.replay.to_owned() {
-        Some(name) => name,
+pub async fn download_replay(
+    backend: &dyn LeaderboardBackend,
+    score: &Score,
+) -> Result<ReplayFile> {
+    let replay_data = match &score.replay {
+        Some(replay) => replay,
         None => {
             return Err(anyhow!(
                 "No valid parse url, replay name is None. db score?"
@@ -20,34 +42,52 @@ pub async fn download_replay(client: &Client, score: &Score) -> Result<()> {
         }
     };
 
-    let url = match reqwest::Url::parse(&format!(
-        "https://{}/parse/files/{}/{}",
-        &SETTINGS.read().unwrap().parse.domain,
-        &SETTINGS.read().unwrap().parse.appid,
-        replay_data.name
-    )) {
-        Ok(url) => url,
-        Err(err) => return Err(anyhow!("Failed to parse replay url: {}", err)),
-    };
+    let bytes = backend.download_replay(replay_data).await?;
 
-    let res = match raw_request(client, url).await {
-        Ok(res) => res,
-        Err(err) => return Err(anyhow!("Failed to download replay: {}", err)),
-    };
+    if let Err(err) = fs::create_dir_all(get_path(score)) {
+        return Err(anyhow!("Failed to create dir for replay: {}", err));
+    }
+
+    let path = get_path(score) + &get_name(score);
+    if let Err(err) = fs::write(&path, &bytes) {
+        return Err(anyhow!("Failed to save replay onto disk: {}", err));
+    }
 
-    if let Ok(bytes) = res.bytes().await {
-        match fs::create_dir_all(&get_path(&score)) {
-            Err(err) => return Err(anyhow!("Failed to create dir for replay: {}", err)),
-            _ => (),
-        };
+    Ok(ReplayFile {
+        path,
+        size: bytes.len() as i64,
+        sha256: get_hash(&bytes),
+        parse_name: replay_data.name.clone(),
+        downloaded_at: Utc::now(),
+    })
+}
 
-        match fs::write(get_path(&score) + &get_name(&score), bytes) {
-            Err(err) => return Err(anyhow!("Failed to save replay onto disk: {}", err)),
-            _ => (),
-        };
+/// Reads the saved replay for a world record read from the database
+///
+/// Errors if the score has no saved replay, or if the file no longer matches its hash
+pub async fn read_replay(conn: &mut SqliteConnection, score: &Score) -> Result<Vec<u8>> {
+    let record_id = score
+        .record_id
+        .ok_or(anyhow!("Score has no record id, not from the db?"))?;
+
+    let file = db::get_replay(conn, record_id)
+        .await?
+        .ok_or(anyhow!("No saved replay for record: {}", record_id))?;
+
+    let bytes = match fs::read(&file.path) {
+        Ok(bytes) => bytes,
+        Err(err) => return Err(anyhow!("Failed to read replay {}: {}", file.path, err)),
+    };
+
+    if get_hash(&bytes) != file.sha256 {
+        return Err(anyhow!("Replay hash mismatch for: {}", file.path));
     }
 
-    Ok(())
+    Ok(bytes)
+}
+
+fn get_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
 }
 
 fn get_path(score: &Score) -> String {
@@ -55,7 +95,7 @@ fn get_path(score: &Score) -> String {
 }
 
 fn get_name(score: &Score) -> String {
-    let file_count: isize = match fs::read_dir(get_path(&score)) {
+    let file_count: isize = match fs::read_dir(get_path(score)) {
         Ok(files) => files.count() as isize,
         Err(err) => {
             println!(
@@ -78,3 +118,45 @@ fn test_path() {
 
     assert_eq!("./replays/test/", &get_path(&score));
 }
+
+#[test]
+fn test_hash() {
+    assert_eq!(
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        get_hash(&[])
+    );
+}
+
+#[tokio::test]
+async fn test_read_replay() {
+    use crate::test_util::get_fake_score;
+    use sqlx::Connection;
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    db::create_tables(&mut conn).await;
+
+    let mut score = get_fake_score(10.0..15.0);
+    score.record_id = Some(db::update_level(&mut conn, &score).await.unwrap());
+
+    let path = std::env::temp_dir().join("miuu_read_replay_test.replay");
+    let bytes = vec![1, 2, 3];
+    fs::write(&path, &bytes).unwrap();
+
+    let file = ReplayFile {
+        path: path.to_string_lossy().to_string(),
+        size: bytes.len() as i64,
+        sha256: get_hash(&bytes),
+        parse_name: "REPLAY_USERID_USERNAME.replay".into(),
+        downloaded_at: Utc::now(),
+    };
+    db::insert_replay(&mut conn, score.record_id.unwrap(), &file)
+        .await
+        .unwrap();
+
+    assert_eq!(bytes, read_replay(&mut conn, &score).await.unwrap());
+
+    fs::write(&path, [4, 5, 6]).unwrap();
+    assert!(read_replay(&mut conn, &score).await.is_err());
+
+    fs::remove_file(path).unwrap();
+}
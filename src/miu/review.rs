@@ -0,0 +1,78 @@
+//! Plausibility checks for new world records
+//!
+//! Anything that fails a check is held for a moderator instead of being announced
+
+use crate::{config::Review, miu::score::Score};
+
+/// Runs every configured check on a new world record
+///
+/// Returns why the record is suspicious, empty if it looks fine
+pub fn check_plausibility(new: &Score, prev: &Score, review: &Review) -> Vec<String> {
+    let mut reasons: Vec<String> = vec![];
+
+    if let Some(max_percent) = review.max_improvement_percent {
+        let improvement = (prev.time - new.time) / prev.time * 100.0;
+
+        if improvement > max_percent {
+            reasons.push(format!(
+                "Improvement of {:.2}% is above {:.2}%",
+                improvement, max_percent
+            ));
+        }
+    }
+
+    if let Some(floor) = review
+        .level_floors
+        .get(new.map_id.trim_start_matches("SP_"))
+    {
+        if new.time < *floor {
+            reasons.push(format!(
+                "Time {} is below the level floor of {}",
+                new.time, floor
+            ));
+        }
+    }
+
+    if !review.allowed_platforms.is_empty() && !review.allowed_platforms.contains(&new.platform) {
+        reasons.push(format!("Unknown platform: {}", new.platform));
+    }
+
+    if review.require_replay && new.replay.is_none() {
+        reasons.push(String::from("Replay is missing"));
+    }
+
+    reasons
+}
+
+#[test]
+fn test_check_plausibility() {
+    use crate::test_util::get_fake_score;
+    use std::collections::HashMap;
+
+    let review = Review {
+        webhooks: vec![],
+        max_improvement_percent: Some(10.0),
+        level_floors: HashMap::from([(String::from("test_level"), 5.0)]),
+        allowed_platforms: vec![String::from("PC")],
+        require_replay: true,
+    };
+
+    let mut prev = get_fake_score(10.0..11.0);
+    prev.time = 10.0;
+    prev.map_id = String::from("SP_test_level");
+
+    let mut new = prev.clone();
+    new.time = 9.5;
+    new.platform = String::from("PC");
+    new.replay = Some(crate::miu::score::Replay {
+        r#type: String::from("File"),
+        name: String::from("replay"),
+        url: String::new(),
+    });
+    assert!(check_plausibility(&new, &prev, &review).is_empty());
+
+    new.time = 4.0;
+    new.platform = String::from("Toaster");
+    new.replay = None;
+    assert_eq!(4, check_plausibility(&new, &prev, &review).len());
+}
@@ -2,12 +2,12 @@
 
 use std::time::Duration;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use chrono::{DateTime, Utc};
 
 /// A Replay struct
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Replay {
     /// The replay type, often a file
     #[serde(rename = "__type")]
@@ -23,7 +23,7 @@ pub struct Replay {
 /// A Score struct
 ///
 /// Common across normal leaderboards and weekly challenges leaderboards
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Score {
     /// The time of the score
     pub time: f32,
@@ -63,6 +63,12 @@ pub struct Score {
     /// Parse internal object id
     #[serde(rename = "objectId")]
     pub object_id: Option<String>,
+
+    /// The row id in the `world_records` table
+    ///
+    /// `None` if the score hasn't been read from the database
+    #[serde(skip)]
+    pub record_id: Option<i64>,
 }
 
 /// A special score wrapper for weekly WR recaps
@@ -93,9 +99,33 @@ pub struct Results<T> {
     ///
     ///`None` if the response was successful
     pub error: Option<String>,
+    /// The total count of matching objects
+    ///
+    /// Only `Some` if the query asked for it with `ParseQuery::count`
+    pub count: Option<u64>,
 }
 
 impl Score {
+    /// Creates an empty score, used as the previous world record for a level that had none
+    ///
+    /// Has the same time as `new`, so it shows no improvement
+    pub fn empty(new: &Score) -> Self {
+        Score {
+            time: new.time,
+            user_id: String::new(),
+            username: String::from("None"),
+            map_id: new.map_id.clone(),
+            skin_used: String::new(),
+            replay_version: 0,
+            platform: String::from("-"),
+            replay: None,
+            created_at: new.created_at,
+            updated_at: new.updated_at,
+            object_id: None,
+            record_id: None,
+        }
+    }
+
     /// Returns a formatted time
     ///
     /// In the format of: `MM:SS:MS` only if the time is above a minute,
@@ -121,6 +151,7 @@ impl Score {
 }
 
 #[test]
+#[allow(clippy::excessive_precision)]
 fn test_formatted_time() {
     use crate::test_util::get_fake_score;
 
@@ -138,3 +169,22 @@ fn test_formatted_time() {
     assert_eq!("02:05.242966", score_3.get_formatted_time());
     assert_eq!("40:21.592041", score_4.get_formatted_time());
 }
+
+#[test]
+fn test_generic_results() {
+    #[derive(Deserialize)]
+    struct Stats {
+        #[serde(rename = "LevelID")]
+        level_id: String,
+    }
+
+    let resp: Results<Stats> =
+        serde_json::from_str(r#"{"results":[{"LevelID":"CHALLENGE_DATA"}],"count":12}"#).unwrap();
+    assert_eq!("CHALLENGE_DATA", resp.results.unwrap()[0].level_id);
+    assert_eq!(Some(12), resp.count);
+
+    let err: Results<Stats> =
+        serde_json::from_str(r#"{"code":155,"error":"Request limit exceeded"}"#).unwrap();
+    assert!(err.results.is_none());
+    assert_eq!(Some(155), err.code);
+}
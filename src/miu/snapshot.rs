@@ -0,0 +1,100 @@
+//! Periodic snapshots of the top scores for every level
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use sqlx::SqliteConnection;
+
+use crate::{backend::LeaderboardBackend, db, miu::score::Score};
+
+/// The top scores of a level at a given time
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// The level id, without the `SP_` prefix
+    pub level: String,
+    /// When the snapshot was taken, in Utc
+    pub taken_at: DateTime<Utc>,
+    /// The scores, ordered by rank with the world record first
+    pub scores: Vec<Score>,
+}
+
+impl Snapshot {
+    /// Returns the time gap between #1 and #2, `None` if there is no #2
+    pub fn gap(&self) -> Option<f32> {
+        match (self.scores.first(), self.scores.get(1)) {
+            (Some(first), Some(second)) => Some(second.time - first.time),
+            _ => None,
+        }
+    }
+
+    /// Returns the top 3 scores
+    pub fn podium(&self) -> &[Score] {
+        &self.scores[..self.scores.len().min(3)]
+    }
+
+    /// Returns the rank of a user, starting at 1
+    pub fn rank_of(&self, user_id: &str) -> Option<usize> {
+        self.scores
+            .iter()
+            .position(|s| s.user_id == user_id)
+            .map(|i| i + 1)
+    }
+}
+
+/// Fetches and saves the top `top_n` scores for every level
+///
+/// Returns how many levels got a snapshot, alongside any errors
+pub async fn take_snapshots(
+    backend: &dyn LeaderboardBackend,
+    conn: &mut SqliteConnection,
+    levels: &[String],
+    top_n: u32,
+) -> (usize, Vec<String>) {
+    let taken_at = Utc::now();
+    let mut taken: usize = 0;
+    let mut errors: Vec<String> = vec![];
+
+    for level in levels {
+        let result: Result<()> = async {
+            let scores = backend.fetch_top(level, top_n).await?;
+            db::insert_snapshot(conn, level, taken_at, &scores).await
+        }
+        .await;
+
+        match result {
+            Ok(_) => taken += 1,
+            Err(err) => {
+                println!("{}: {:#}", "Failed to snapshot".red().bold(), err);
+                errors.push(format!("Failed to snapshot {}: {:#}", level, err));
+            }
+        }
+    }
+
+    println!(
+        "{}: {} levels",
+        "Took leaderboard snapshots".green().bold(),
+        taken
+    );
+
+    (taken, errors)
+}
+
+#[test]
+fn test_snapshot_gap() {
+    use crate::test_util::get_fake_score;
+
+    let mut snapshot = Snapshot {
+        level: "test_level".into(),
+        taken_at: Utc::now(),
+        scores: vec![get_fake_score(5.0..6.0)],
+    };
+    assert_eq!(None, snapshot.gap());
+
+    snapshot.scores[0].time = 5.0;
+    let mut second = get_fake_score(6.0..7.0);
+    second.time = 6.5;
+    snapshot.scores.push(second);
+
+    assert_eq!(Some(1.5), snapshot.gap());
+    assert_eq!(2, snapshot.podium().len());
+}
@@ -1,78 +1,35 @@
 //! Fetches and handles weekly challenges
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
-use reqwest::Client;
 use sqlx::SqliteConnection;
 
 use crate::{
-    config::SETTINGS,
+    backend::LeaderboardBackend,
     db,
     miu::{
         score::Score,
-        weekly_data::{self, ScoreBucket},
+        weekly_data::{self, Challenge, ChallengeLevel, NameLang, ScoreBucket},
     },
-    request::make_request,
 };
 
 /// Fetches the world record for a given week state and scorebucket
-pub async fn fetch(client: &Client, state: &WeekState, bucket: &ScoreBucket) -> Result<Vec<Score>> {
+pub async fn fetch(
+    backend: &dyn LeaderboardBackend,
+    state: &WeekState,
+    bucket: &ScoreBucket,
+) -> Result<Vec<Score>> {
     let bucket_state = match state {
-        WeekState::Current => bucket.current.clone(),
-        WeekState::Previous => bucket.previous.clone(),
+        WeekState::Current => &bucket.current,
+        WeekState::Previous => &bucket.previous,
     };
-    let (start, end) = (bucket_state.start_date, bucket_state.end_date);
-    let base_params: Vec<(&str, &str)> = vec![("order", "time"), ("limit", "1")];
 
-    let mut scores: Vec<Score> = Vec::with_capacity(5);
+    let mut scores = backend.fetch_weekly_scores(bucket_state).await?;
 
-    for (i, level) in bucket_state.levels.into_iter().enumerate() {
-        let map_id = format!("{}{}", bucket_state.chapter_set, i);
-
-        let where_value = format!(
-            r#"
-            {{
-                "mapID":"{}", 
-                "updatedAt":{{
-                    "$gte":{{
-                        "__type":"Date",
-                        "iso":"{}"
-                    }}, 
-                    "$lt":{{
-                        "__type":"Date",
-                        "iso":"{}"
-                    }}
-                }}
-            }}
-            "#,
-            map_id,
-            start.format("%+").to_string(),
-            end.format("%+").to_string()
-        );
-
-        let mut params = base_params.clone();
-        params.push(("where", &where_value));
-
-        let score = match make_request(
-            client,
-            params,
-            None,
-            Some(SETTINGS.read().unwrap().parse.weekly.class_name.clone()),
-        )
-        .await
-        {
-            Ok(resp) => resp,
-            Err(err) => return Err(anyhow!("Failed to fetch weekly: {}", err)),
-        };
-        let mut score = score
-            .first()
-            .ok_or(anyhow!("No elements on scores"))?
-            .clone();
-
-        // Since weekly challenge map_ids are A/B#, we just quickly convert them back
-        score.map_id = level.name;
-
-        scores.push(score);
+    // Since weekly challenge map_ids are A/B#, we just quickly convert them back
+    for (score, level) in scores.iter_mut().zip(&bucket_state.levels) {
+        score.map_id = level.name.clone();
     }
 
     Ok(scores)
@@ -83,9 +40,9 @@ pub async fn fetch(client: &Client, state: &WeekState, bucket: &ScoreBucket) ->
 /// Uses the saved end date in the database and compares to the server
 pub async fn check(
     conn: &mut SqliteConnection,
-    client: &Client,
+    backend: &dyn LeaderboardBackend,
 ) -> (bool, Option<weekly_data::Weekly>) {
-    let newest_data = match weekly_data::Weekly::fetch(client).await {
+    let newest_data = match backend.fetch_weekly().await {
         Ok(data) => data,
         Err(err) => {
             println!(
@@ -110,6 +67,37 @@ pub async fn check(
     (false, None)
 }
 
+/// A finished weekly challenge, as saved in the weekly history
+#[derive(Debug, Clone)]
+pub struct WeeklyHistory {
+    /// The challenge id
+    pub challenge_id: String,
+    /// The english name of the challenge
+    pub name: String,
+    /// The start date of the challenge, in Utc
+    pub start_date: DateTime<Utc>,
+    /// The end date of the challenge, in Utc
+    pub end_date: DateTime<Utc>,
+    /// The levels, contains physics mods and level titles
+    pub levels: Vec<ChallengeLevel>,
+    /// The winning score for every level, in level order
+    pub scores: Vec<Score>,
+}
+
+impl WeeklyHistory {
+    /// Creates a history entry from a challenge and its winning scores
+    pub fn new(challenge: &Challenge, scores: Vec<Score>) -> Self {
+        WeeklyHistory {
+            challenge_id: challenge.challenge_id.clone(),
+            name: challenge.get_name(NameLang::En),
+            start_date: challenge.start_date,
+            end_date: challenge.end_date,
+            levels: challenge.levels.clone(),
+            scores,
+        }
+    }
+}
+
 /// The week state
 #[allow(dead_code)]
 pub enum WeekState {
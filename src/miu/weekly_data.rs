@@ -4,11 +4,15 @@ use std::{collections::HashMap, str::FromStr};
 
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
-use reqwest::{Client, Url};
-use serde::Deserialize;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, EnumMap};
 
-use crate::{config::SETTINGS, miu::score::Results, request::raw_request};
+use crate::{
+    config::SETTINGS,
+    miu::score::Results,
+    request::{make_request, query::ParseQuery},
+};
 
 /// An entire weekly challenge
 #[derive(Debug, Deserialize, Clone)]
@@ -37,6 +41,7 @@ pub struct Weekly {
 impl FromStr for Weekly {
     type Err = String;
 
+    #[allow(clippy::useless_format)]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mid = match serde_json::from_str::<Results<MidWeekly>>(s) {
             Ok(mid) => match mid.results {
@@ -52,18 +57,7 @@ impl FromStr for Weekly {
             Err(err) => return Err(format!("Failed to parse mid weekly: {}", err)),
         };
 
-        let score_buckets = match serde_json::from_str::<ScoreBucket>(&mid.score_buckets) {
-            Ok(s_bucket) => s_bucket,
-            Err(err) => return Err(format!("Failed to parse score bucket: {}", err)),
-        };
-
-        Ok(Weekly {
-            object_id: mid.object_id,
-            level_id: mid.level_id,
-            created_at: mid.created_at,
-            updated_at: mid.updated_at,
-            score_buckets,
-        })
+        mid.into_weekly()
     }
 }
 
@@ -72,31 +66,27 @@ impl Weekly {
     ///
     /// Fetches and parses the data from the server
     pub async fn fetch(client: &Client) -> Result<Weekly> {
-        let url = match Url::parse_with_params(
-            &format!(
-                "https://{}/parse/classes/{}",
-                &SETTINGS.read().unwrap().parse.domain,
-                &SETTINGS.read().unwrap().parse.weekly.class_name_stats
-            ),
-            &[("where", r#"{"LevelID": "CHALLENGE_DATA"}"#)],
-        ) {
-            Ok(url) => url,
-            Err(err) => return Err(anyhow!("Failed to build weekly data url: {}", err)),
-        };
-
-        let resp = match raw_request(client, url).await {
-            Ok(resp) => resp,
+        let class_name = SETTINGS
+            .read()
+            .unwrap()
+            .parse
+            .weekly
+            .class_name_stats
+            .clone();
+
+        let query = ParseQuery::new().equal_to("LevelID", "CHALLENGE_DATA");
+
+        let mid = match make_request::<MidWeekly>(client, &query, None, Some(class_name)).await {
+            Ok(resp) => resp.results.unwrap_or_default(),
             Err(err) => return Err(anyhow!("Failed to fetch weekly data: {}", err)),
         };
 
-        if let Ok(text) = resp.text().await {
-            return match Weekly::from_str(&text) {
-                Ok(weekly) => Ok(weekly),
-                Err(err) => Err(anyhow!("Failed to get weekly from str: {}", err)),
-            };
+        match mid.into_iter().next() {
+            Some(mid) => mid
+                .into_weekly()
+                .map_err(|err| anyhow!("Failed to get weekly from response: {}", err)),
+            None => Err(anyhow!("Results is empty when fetching weekly data")),
         }
-
-        Err(anyhow!("Failed to return weekly data"))
     }
 }
 
@@ -120,6 +110,24 @@ struct MidWeekly {
     score_buckets: String,
 }
 
+impl MidWeekly {
+    /// Parses the inner score bucket json string
+    fn into_weekly(self) -> Result<Weekly, String> {
+        let score_buckets = match serde_json::from_str::<ScoreBucket>(&self.score_buckets) {
+            Ok(s_bucket) => s_bucket,
+            Err(err) => return Err(format!("Failed to parse score bucket: {}", err)),
+        };
+
+        Ok(Weekly {
+            object_id: self.object_id,
+            level_id: self.level_id,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            score_buckets,
+        })
+    }
+}
+
 /// Holds the current and previous challenge
 ///
 /// And some internal sheet ids and stuff
@@ -182,7 +190,7 @@ impl Challenge {
 
 /// A level in a challenge
 #[serde_as]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ChallengeLevel {
     /// The fancy level title
     pub name: String,
@@ -196,7 +204,7 @@ pub struct ChallengeLevel {
 /// All physics mods to ever exist.
 ///
 /// Every mod has a value with it.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub enum PhysicsMod {
     /// Changes the gravity
     #[serde(rename = "gravity")]
@@ -349,6 +357,7 @@ fn float_to_perct(f: &f32) -> String {
     format!("{}%", f * 100.0)
 }
 
+#[allow(clippy::to_string_trait_impl, clippy::useless_format)]
 impl ToString for PhysicsMod {
     fn to_string(&self) -> String {
         match self {
@@ -424,6 +433,7 @@ pub enum NameLang {
     /// Turkey
     Tr,
 }
+#[allow(clippy::to_string_trait_impl)]
 impl ToString for NameLang {
     fn to_string(&self) -> String {
         String::from(match self {
@@ -0,0 +1,393 @@
+//! Reloads the settings and level metadata while the checker is running
+//!
+//! Reloads when any of the watched files change, or when the process gets a `SIGHUP`
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use colored::Colorize;
+
+use crate::{
+    config::{self, Discord, Parse, Settings},
+    db,
+    metadata::{load_levels_from, Levels, METADATA_PATH},
+    scheduler, State,
+};
+
+/// Watches the config and metadata files for changes
+pub struct Reloader {
+    /// Every watched file and when it was last modified, `None` if it doesn't exist
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Set when a `SIGHUP` is received
+    signaled: Arc<AtomicBool>,
+}
+
+impl Reloader {
+    /// Starts watching every config file and the level metadata file
+    ///
+    /// Also listens for `SIGHUP` on unix, so must be created inside a tokio runtime
+    pub fn start() -> Self {
+        let mut paths = config::get_config_paths();
+        paths.push(PathBuf::from(METADATA_PATH));
+
+        let reloader = Reloader {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = get_modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+            signaled: Arc::new(AtomicBool::new(false)),
+        };
+
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let signaled = reloader.signaled.clone();
+            match signal(SignalKind::hangup()) {
+                Ok(mut hangup) => {
+                    tokio::spawn(async move {
+                        while hangup.recv().await.is_some() {
+                            signaled.store(true, Ordering::Relaxed);
+                        }
+                    });
+                }
+                Err(err) => println!("{}: {}", "Failed to listen for SIGHUP".red(), err),
+            }
+        }
+
+        reloader
+    }
+
+    /// Returns true if any watched file changed or a `SIGHUP` was received since the last call
+    pub fn should_reload(&mut self) -> bool {
+        let mut changed = self.signaled.swap(false, Ordering::Relaxed);
+
+        for (path, modified) in &mut self.files {
+            let current = get_modified(path);
+            if current != *modified {
+                *modified = current;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
+
+fn get_modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reloads the settings and levels, logging everything that changed
+///
+/// Keeps the old settings or levels if either fails to load
+pub async fn reload(state: &mut State) {
+    println!("{}", "Reloading config and level metadata".yellow().bold());
+
+    match config::load_settings() {
+        Ok(new) => {
+            let limits = new.parse.limits.clone();
+            let limits_changed = {
+                let mut settings = config::SETTINGS.write().unwrap();
+                for change in get_settings_changes(&settings, &new) {
+                    println!("- {}: {}", "Config changed".yellow(), change);
+                }
+
+                let limits_changed = settings.parse.limits != new.parse.limits;
+                *settings = new;
+                limits_changed
+            };
+
+            // Rebuilt after the settings lock is released, the scheduler reads them when first used
+            if limits_changed {
+                scheduler::rebuild(&limits);
+            }
+        }
+        Err(err) => println!(
+            "{}: {:#}",
+            "Failed to reload config, keeping the old one".red().bold(),
+            err
+        ),
+    }
+
+    reload_levels(state, METADATA_PATH).await;
+}
+
+/// Reloads the levels from a metadata file, keeps the old levels if it fails to load
+async fn reload_levels(state: &mut State, path: &str) {
+    let levels = match load_levels_from(&mut state.conn, path).await {
+        Ok(levels) => levels,
+        Err(err) => {
+            println!(
+                "{}: {:#}",
+                "Failed to reload levels, keeping the old ones".red().bold(),
+                err
+            );
+            return;
+        }
+    };
+
+    for change in get_level_changes(&state.levels, &levels) {
+        println!("- {}: {}", "Levels changed".yellow(), change);
+    }
+
+    // Levels that weren't checked before might already have confirmed world records
+    let added: Vec<String> = levels
+        .active_ids()
+        .into_iter()
+        .filter(|level| !state.confirmed_wrs.contains_key(&format!("SP_{}", level)))
+        .collect();
+    state
+        .confirmed_wrs
+        .extend(db::get_all(&mut state.conn, &added).await);
+
+    state.levels = levels;
+}
+
+/// Describes every setting that differs between the old and new settings
+///
+/// Webhook urls and Parse credentials are never printed, only that they changed
+pub fn get_settings_changes(old: &Settings, new: &Settings) -> Vec<String> {
+    let mut changes: Vec<String> = vec![];
+
+    if old.loop_wait_seconds != new.loop_wait_seconds {
+        changes.push(format!(
+            "loop_wait_seconds {} -> {}",
+            old.loop_wait_seconds, new.loop_wait_seconds
+        ));
+    }
+    if old.discord.webhooks != new.discord.webhooks {
+        changes.push(format!(
+            "discord.webhooks {} -> {} webhook(s)",
+            old.discord.webhooks.len(),
+            new.discord.webhooks.len()
+        ));
+    }
+    if old.discord.weekly_webhooks != new.discord.weekly_webhooks {
+        changes.push(format!(
+            "discord.weekly_webhooks {} -> {} webhook(s)",
+            old.discord.weekly_webhooks.len(),
+            new.discord.weekly_webhooks.len()
+        ));
+    }
+
+    // Fields that are reported on their own are left out of their section
+    let discord = Discord {
+        webhooks: old.discord.webhooks.clone(),
+        weekly_webhooks: old.discord.weekly_webhooks.clone(),
+        ..new.discord.clone()
+    };
+    let parse = Parse {
+        limits: old.parse.limits.clone(),
+        ..new.parse.clone()
+    };
+
+    let sections = [
+        ("kuma_push_url", old.kuma_push_url != new.kuma_push_url),
+        ("discord", old.discord != discord),
+        ("parse", old.parse != parse),
+        ("snapshot", old.snapshot != new.snapshot),
+        ("review", old.review != new.review),
+        ("discovery", old.discovery != new.discovery),
+    ];
+    for (name, changed) in sections {
+        if changed {
+            changes.push(format!("{} updated", name));
+        }
+    }
+
+    // These are only read on startup
+    if old.database_url != new.database_url {
+        changes.push(String::from("database_url updated, requires a restart"));
+    }
+    if old.fixture_dir != new.fixture_dir {
+        changes.push(String::from("fixture_dir updated, requires a restart"));
+    }
+    if old.parse.limits != new.parse.limits {
+        changes.push(String::from(
+            "parse.limits updated, rebuilt the request scheduler",
+        ));
+    }
+
+    changes
+}
+
+/// Describes every level that was added, removed or updated
+pub fn get_level_changes(old: &Levels, new: &Levels) -> Vec<String> {
+    let mut changes: Vec<String> = vec![];
+
+    for level in new.iter() {
+        match old.get(&level.id) {
+            None => changes.push(format!("added {}", level.id)),
+            Some(old_level) if old_level.retired != level.retired => {
+                changes.push(match level.retired {
+                    true => format!("retired {}", level.id),
+                    false => format!("unretired {}", level.id),
+                })
+            }
+            Some(old_level) if old_level != level => changes.push(format!("updated {}", level.id)),
+            Some(_) => (),
+        }
+    }
+
+    for level in old.iter() {
+        if !new.contains(&level.id) {
+            changes.push(format!("removed {}", level.id));
+        }
+    }
+
+    changes
+}
+
+#[test]
+fn test_level_changes() {
+    let old = Levels::parse(
+        r#"{ "version": 1, "levels": [
+            { "id": "a", "title": "A" },
+            { "id": "b", "title": "B" },
+            { "id": "c", "title": "C" }
+        ] }"#,
+    )
+    .unwrap();
+    let new = Levels::parse(
+        r#"{ "version": 1, "levels": [
+            { "id": "a", "title": "A" },
+            { "id": "b", "title": "B", "retired": true },
+            { "id": "d", "title": "D", "gold_time": 10.0 }
+        ] }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec!["retired b", "added d", "removed c"],
+        get_level_changes(&old, &new)
+    );
+    assert!(get_level_changes(&new, &new).is_empty());
+}
+
+#[test]
+fn test_should_reload() {
+    let path = std::env::temp_dir().join(format!("miuu_reload_test_{}.toml", std::process::id()));
+    fs::write(&path, "loop_wait_seconds = 1").unwrap();
+
+    let mut reloader = Reloader {
+        files: vec![(path.clone(), get_modified(&path))],
+        signaled: Arc::new(AtomicBool::new(false)),
+    };
+    assert!(!reloader.should_reload());
+
+    reloader.signaled.store(true, Ordering::Relaxed);
+    assert!(reloader.should_reload());
+    assert!(!reloader.should_reload());
+
+    fs::remove_file(&path).unwrap();
+    assert!(reloader.should_reload());
+    assert!(!reloader.should_reload());
+
+    fs::write(&path, "loop_wait_seconds = 2").unwrap();
+    assert!(reloader.should_reload());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_reload() {
+    use crate::{
+        backend::fixture::FixtureBackend,
+        db::{create_tables, insert_record, RecordStatus},
+        test_util::{get_fake_score, init_test_settings},
+    };
+    use reqwest::Client;
+    use sqlx::{Connection, SqliteConnection};
+
+    init_test_settings();
+
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create_tables(&mut conn).await;
+
+    let mut wr = get_fake_score(5.0..6.0);
+    wr.map_id = String::from("SP_bunny_slope");
+    insert_record(&mut conn, &wr, RecordStatus::Confirmed, &[])
+        .await
+        .unwrap();
+
+    let levels =
+        Levels::parse(r#"{ "version": 1, "levels": [{ "id": "old", "title": "Old" }] }"#).unwrap();
+    let mut state = State::from_parts(
+        Client::new(),
+        Box::new(FixtureBackend::default()),
+        conn,
+        levels,
+    )
+    .await;
+
+    // A broken metadata file keeps the old levels
+    reload_levels(&mut state, "./missing_levels.json").await;
+    assert_eq!(vec!["old"], state.levels.ids());
+
+    reload(&mut state).await;
+    assert!(!state.levels.contains("old"));
+    assert!(state.levels.contains("bunny_slope"));
+    assert!(state.confirmed_wrs.contains_key("SP_bunny_slope"));
+}
+
+#[test]
+fn test_settings_changes() {
+    let old: Settings = ::config::Config::builder()
+        .add_source(::config::File::from_str(
+            r#"
+            database_url = "./db.sqlite"
+            loop_wait_seconds = 120
+
+            [discord]
+            webhooks = ["https://discord.com/api/webhooks/1/a"]
+            weekly_webhooks = []
+
+            [parse]
+            domain = "www.example.com"
+            appid = "appid"
+            class_name = "class"
+
+            [parse.weekly]
+            class_name = "challenge"
+            class_name_stats = "challenge_stats"
+            "#,
+            ::config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap()
+        .try_deserialize()
+        .unwrap();
+    assert!(get_settings_changes(&old, &old.clone()).is_empty());
+
+    // Only the limits changed, so the rest of `parse` isn't reported
+    let mut new = old.clone();
+    new.parse.limits.max_concurrency += 1;
+    assert_eq!(
+        vec!["parse.limits updated, rebuilt the request scheduler"],
+        get_settings_changes(&old, &new)
+    );
+
+    let mut new = old.clone();
+    new.discord.webhooks.clear();
+    new.discord.dry_run = true;
+    new.parse.appid = String::from("new_appid");
+    assert_eq!(
+        vec![
+            "discord.webhooks 1 -> 0 webhook(s)",
+            "discord updated",
+            "parse updated"
+        ],
+        get_settings_changes(&old, &new)
+    );
+}
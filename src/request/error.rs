@@ -0,0 +1,102 @@
+//! Errors returned by the Parse client
+
+use std::{error::Error, fmt, time::Duration};
+
+use reqwest::StatusCode;
+
+/// Everything that can go wrong when talking to Parse
+///
+/// Can be downcast from an `anyhow::Error` to decide how to handle a failure
+#[derive(Debug)]
+pub enum ParseError {
+    /// The request url could not be built
+    InvalidUrl(String),
+    /// The request never got a response, connection or timeout errors
+    Network(reqwest::Error),
+    /// The server responded with an unsuccessful HTTP status
+    Status {
+        /// The status code
+        status: StatusCode,
+        /// The `Retry-After` header, if sent
+        retry_after: Option<Duration>,
+    },
+    /// Parse itself returned an error object
+    Parse {
+        /// The Parse error code
+        code: u32,
+        /// The error message
+        message: String,
+    },
+    /// The response body could not be read or deserialized
+    Decode(String),
+    /// The request was successful but returned nothing, for the given context
+    Empty(String),
+}
+
+impl ParseError {
+    /// Returns true if sending the same request again might succeed
+    ///
+    /// Parse codes `1` InternalServerError, `100` ConnectionFailed, `124` Timeout, `155` RequestLimitExceeded
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ParseError::Network(_) => true,
+            ParseError::Status { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            ParseError::Parse { code, .. } => matches!(code, 1 | 100 | 124 | 155),
+            ParseError::InvalidUrl(_) | ParseError::Decode(_) | ParseError::Empty(_) => false,
+        }
+    }
+
+    /// Returns true if the request worked but there was nothing to return
+    pub fn is_empty(&self) -> bool {
+        matches!(self, ParseError::Empty(_))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidUrl(err) => write!(f, "Url Parse Error: {}", err),
+            ParseError::Network(err) => write!(f, "Request Error: {}", err),
+            ParseError::Status { status, .. } => write!(f, "HTTP {}", status),
+            ParseError::Parse { code, message } => write!(f, "Parse Error: [{}] {}", code, message),
+            ParseError::Decode(err) => write!(f, "Failed to decode response: {}", err),
+            ParseError::Empty(context) => write!(f, "Empty results returned from: {}", context),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Network(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_retryable() {
+    assert!(ParseError::Parse {
+        code: 155,
+        message: String::from("Request limit exceeded")
+    }
+    .is_retryable());
+    assert!(!ParseError::Parse {
+        code: 101,
+        message: String::from("Object not found")
+    }
+    .is_retryable());
+    assert!(ParseError::Status {
+        status: StatusCode::BAD_GATEWAY,
+        retry_after: None
+    }
+    .is_retryable());
+    assert!(!ParseError::Status {
+        status: StatusCode::NOT_FOUND,
+        retry_after: None
+    }
+    .is_retryable());
+    assert!(!ParseError::Empty(String::from("SP_1")).is_retryable());
+}
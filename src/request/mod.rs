@@ -0,0 +1,415 @@
+//! Makes all parse requests
+//!
+//! Used to automatically send some headers alongside the actual request
+
+pub mod error;
+pub mod query;
+
+use std::{future::Future, time::Duration};
+
+use colored::Colorize;
+use rand::Rng;
+use reqwest::{
+    header::{RETRY_AFTER, USER_AGENT},
+    Client, Response, StatusCode, Url,
+};
+use serde::de::DeserializeOwned;
+use tokio::time::sleep;
+
+use crate::{
+    config::{ParseRetry, SETTINGS},
+    miu::score::Results,
+    request::{error::ParseError, query::ParseQuery},
+    scheduler,
+};
+
+fn get_user_agent() -> String {
+    format!(
+        "MIUWRParser/{} VilleOlof/Discord",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+const APPLICATION_ID_HEADER: &str = "X-Parse-Application-Id";
+
+/// Returns the url base for all parse requests
+///
+/// Uses `parse.base_url` if set, otherwise `https://{domain}`
+pub fn get_base_url() -> String {
+    let parse = &SETTINGS.read().unwrap().parse;
+
+    match &parse.base_url {
+        Some(base_url) => base_url.trim_end_matches('/').to_string(),
+        None => format!("https://{}", parse.domain),
+    }
+}
+
+/// Makes a request to the Parse backend at `get_base_url`
+///
+/// Sends the query as url parameters, see `query::ParseQuery`,
+/// and deserializes the results into any type
+///
+/// Can give a path but will otherwise default to `/parse/classes/`
+///
+/// The class can also be give but will default to `parse.class_name` in the settings.
+///
+/// Parse errors are returned as `ParseError::Parse`, so `results` is always `Some` on success
+///
+/// # Example
+///
+/// ```
+/// use miuu_wr_checker_rust::{
+///     miu::score::Score,
+///     request::{make_request, query::ParseQuery},
+/// };
+/// use reqwest::Client;
+///
+/// let client = Client::new();
+/// let query = ParseQuery::new().equal_to("username", "VilleOlof").limit(1);
+///
+/// let results = make_request::<Score>(&client, &query, None, None);
+/// ```
+pub async fn make_request<T: DeserializeOwned>(
+    client: &Client,
+    query: &ParseQuery,
+    path: Option<&str>,
+    class: Option<String>,
+) -> Result<Results<T>, ParseError> {
+    let unwrapped_path = path.unwrap_or("/parse/classes/");
+
+    let class_name = class.unwrap_or(SETTINGS.read().unwrap().parse.class_name.clone());
+
+    let url = match Url::parse_with_params(
+        &format!("{}{}{}", get_base_url(), unwrapped_path, class_name),
+        query.to_params(),
+    ) {
+        Ok(url) => url,
+        Err(err) => return Err(ParseError::InvalidUrl(err.to_string())),
+    };
+
+    let (appid, retry) = get_request_settings();
+
+    // Both HTTP and Parse errors are retried here, `send_request` only tries once
+    with_retry(&retry, || async {
+        let res = send_request(client, &url, &appid).await?;
+        read_results(res)
+    })
+    .await
+}
+
+/// Deserializes a Parse response
+///
+/// Parse errors are returned as `ParseError::Parse`,
+/// any other unsuccessful response, like an html error page, as `ParseError::Status`
+fn read_results<T: DeserializeOwned>(res: RawResponse) -> Result<Results<T>, ParseError> {
+    let status_err = ParseError::Status {
+        status: res.status,
+        retry_after: None,
+    };
+
+    let mut resp = match serde_json::from_slice::<Results<T>>(&res.body) {
+        Ok(resp) => resp,
+        Err(_) if !res.status.is_success() => return Err(status_err),
+        Err(err) => return Err(ParseError::Decode(err.to_string())),
+    };
+
+    if let Some(message) = resp.error {
+        return Err(ParseError::Parse {
+            code: resp.code.unwrap_or(0),
+            message,
+        });
+    }
+    if !res.status.is_success() {
+        return Err(status_err);
+    }
+
+    resp.results.get_or_insert_with(Vec::new);
+    Ok(resp)
+}
+
+/// Makes requests page by page until every result, or `max` results, has been fetched
+///
+/// The page size is the limit of the query, or 100 (Parse's default) if not set
+pub async fn make_paginated_request<T: DeserializeOwned>(
+    client: &Client,
+    query: &ParseQuery,
+    path: Option<&str>,
+    class: Option<String>,
+    max: Option<usize>,
+) -> Result<Vec<T>, ParseError> {
+    let page_size = query.get_limit().unwrap_or(100).max(1);
+    let mut results: Vec<T> = vec![];
+
+    loop {
+        let page_query = query
+            .clone()
+            .limit(page_size)
+            .skip(query.get_skip().unwrap_or(0) + results.len() as u32);
+
+        let page = make_request::<T>(client, &page_query, path, class.clone())
+            .await?
+            .results
+            .unwrap_or_default();
+        let page_len = page.len();
+        results.extend(page);
+
+        if page_len < page_size as usize || max.is_some_and(|max| results.len() >= max) {
+            break;
+        }
+    }
+
+    if let Some(max) = max {
+        results.truncate(max);
+    }
+
+    Ok(results)
+}
+
+/// A response with its body already read
+#[derive(Debug)]
+pub struct RawResponse {
+    /// The HTTP status
+    pub status: StatusCode,
+    /// The whole response body
+    pub body: Vec<u8>,
+}
+
+/// Sends a "raw" request to the specified url with some headers
+///
+/// Sends a `USER AGENT` header with the programs identifier
+///
+/// Also sends the appid from settings in a parse header
+///
+/// Waits for `scheduler::SCHEDULER` before every attempt, and holds it until the body is read
+///
+/// Network errors, `429` and `5xx` responses are retried with backoff,
+/// honoring the `Retry-After` header if sent
+pub async fn raw_request(client: &Client, url: Url) -> Result<RawResponse, ParseError> {
+    let (appid, retry) = get_request_settings();
+
+    with_retry(&retry, || send_request(client, &url, &appid)).await
+}
+
+fn get_request_settings() -> (String, ParseRetry) {
+    let parse = &SETTINGS.read().unwrap().parse;
+    (parse.appid.clone(), parse.retry.clone())
+}
+
+/// Sends a single request and reads its body, `429` and `5xx` responses are returned as `ParseError::Status`
+///
+/// The scheduler permit is held until the whole body has been read
+async fn send_request(client: &Client, url: &Url, appid: &str) -> Result<RawResponse, ParseError> {
+    let _permit = scheduler::acquire().await;
+
+    let res = client
+        .get(url.clone())
+        .header(APPLICATION_ID_HEADER, appid)
+        .header(USER_AGENT, get_user_agent())
+        .send()
+        .await
+        .map_err(ParseError::Network)?;
+
+    let status = res.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        return Err(ParseError::Status {
+            status,
+            retry_after: get_retry_after(&res),
+        });
+    }
+
+    let body = res.bytes().await.map_err(ParseError::Network)?;
+
+    Ok(RawResponse {
+        status,
+        body: body.to_vec(),
+    })
+}
+
+/// Runs `send` until it succeeds, fails with an error that isn't retryable,
+/// or `retry.max_retries` retries have been used up
+///
+/// See `ParseError::is_retryable` for what gets retried
+async fn with_retry<T, F, Fut>(retry: &ParseRetry, mut send: F) -> Result<T, ParseError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ParseError>>,
+{
+    let mut attempt: u32 = 0;
+
+    loop {
+        let err = match send().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        if !err.is_retryable() || attempt >= retry.max_retries {
+            return Err(err);
+        }
+
+        let delay = get_delay(retry, attempt, &err);
+        log_retry(attempt, retry, delay, &err);
+
+        sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Returns how long to wait before retrying after `err`
+///
+/// Uses the `Retry-After` header if sent, otherwise the backoff, never above the max delay
+fn get_delay(retry: &ParseRetry, attempt: u32, err: &ParseError) -> Duration {
+    match err {
+        ParseError::Status {
+            retry_after: Some(retry_after),
+            ..
+        } => (*retry_after).min(Duration::from_millis(retry.max_delay_ms)),
+        _ => get_backoff(retry, attempt),
+    }
+}
+
+/// Returns how long to wait before the given retry attempt
+///
+/// Doubles the base delay every attempt, capped at the max delay,
+/// and then picks a random delay between half and all of it
+fn get_backoff(retry: &ParseRetry, attempt: u32) -> Duration {
+    let delay = retry
+        .base_delay_ms
+        .saturating_mul(2u64.saturating_pow(attempt))
+        .min(retry.max_delay_ms);
+
+    Duration::from_millis(rand::thread_rng().gen_range(delay / 2..=delay))
+}
+
+/// Reads the `Retry-After` header, in seconds
+fn get_retry_after(res: &Response) -> Option<Duration> {
+    res.headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+fn log_retry(attempt: u32, retry: &ParseRetry, delay: Duration, reason: &ParseError) {
+    println!(
+        "{} ({}/{}) in {}ms: {}",
+        "Retrying parse request".yellow(),
+        attempt + 1,
+        retry.max_retries,
+        delay.as_millis(),
+        reason
+    );
+}
+
+#[test]
+fn test_backoff() {
+    let retry = ParseRetry {
+        max_retries: 5,
+        base_delay_ms: 100,
+        max_delay_ms: 1000,
+    };
+
+    for _ in 0..20 {
+        let first = get_backoff(&retry, 0).as_millis();
+        assert!((50..=100).contains(&first));
+
+        let third = get_backoff(&retry, 2).as_millis();
+        assert!((200..=400).contains(&third));
+
+        let capped = get_backoff(&retry, 30).as_millis();
+        assert!((500..=1000).contains(&capped));
+    }
+}
+
+#[tokio::test]
+async fn test_single_retry_layer() {
+    let retry = ParseRetry {
+        max_retries: 2,
+        base_delay_ms: 1,
+        max_delay_ms: 5,
+    };
+
+    // HTTP and Parse errors share the same retry budget
+    let mut attempts: u32 = 0;
+    let result: Result<(), ParseError> = with_retry(&retry, || {
+        attempts += 1;
+        let err = match attempts % 2 {
+            0 => ParseError::Parse {
+                code: 155,
+                message: String::from("Request limit exceeded"),
+            },
+            _ => ParseError::Status {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: Some(Duration::from_secs(60)),
+            },
+        };
+        async { Err(err) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(3, attempts);
+
+    // Errors that can't succeed on a retry are returned right away
+    let mut attempts: u32 = 0;
+    let result: Result<(), ParseError> = with_retry(&retry, || {
+        attempts += 1;
+        async { Err(ParseError::Decode(String::from("bad json"))) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(1, attempts);
+
+    let rate_limited = ParseError::Status {
+        status: StatusCode::TOO_MANY_REQUESTS,
+        retry_after: Some(Duration::from_secs(60)),
+    };
+    assert_eq!(
+        Duration::from_millis(5),
+        get_delay(&retry, 0, &rate_limited)
+    );
+}
+
+#[tokio::test]
+async fn test_status_without_parse_error() {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    crate::test_util::init_test_settings();
+
+    // Answers every request with an html 404 page, like a wrong base url
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = Url::parse(&format!(
+        "http://{}/parse/classes/class",
+        listener.local_addr().unwrap()
+    ))
+    .unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 13\r\n\r\n<h1>404</h1>\n")
+                .await;
+        }
+    });
+
+    let res = send_request(&Client::new(), &url, "appid").await.unwrap();
+    match read_results::<crate::miu::score::Score>(res) {
+        Err(ParseError::Status { status, .. }) => assert_eq!(StatusCode::NOT_FOUND, status),
+        other => panic!("Expected a status error, got {:?}", other),
+    }
+
+    // A Parse error body still returns the Parse error
+    let res = RawResponse {
+        status: StatusCode::BAD_REQUEST,
+        body: br#"{"code":102,"error":"Invalid key name"}"#.to_vec(),
+    };
+    assert!(matches!(
+        read_results::<crate::miu::score::Score>(res),
+        Err(ParseError::Parse { code: 102, .. })
+    ));
+}
@@ -0,0 +1,239 @@
+//! A typed builder for Parse queries
+//!
+//! Builds the `where` constraints and the rest of the query parameters,
+//! see <https://docs.parseplatform.org/rest/guide/#queries>
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+/// A Parse query, serialized into url parameters with `ParseQuery::to_params`
+///
+/// # Example
+///
+/// ```
+/// use miuu_wr_checker_rust::request::query::ParseQuery;
+///
+/// let query = ParseQuery::new()
+///     .equal_to("mapID", "SP_Tutorial")
+///     .order("time")
+///     .order_desc("updatedAt")
+///     .limit(1);
+///
+/// assert_eq!(query.get_where(), r#"{"mapID":"SP_Tutorial"}"#);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ParseQuery {
+    constraints: Map<String, Value>,
+    order: Vec<String>,
+    limit: Option<u32>,
+    skip: Option<u32>,
+    keys: Vec<String>,
+    count: bool,
+}
+
+impl ParseQuery {
+    /// Creates an empty query, matching everything
+    pub fn new() -> Self {
+        ParseQuery::default()
+    }
+
+    /// Matches objects where `key` equals `value`
+    pub fn equal_to(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.constraints.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Matches objects where `key` is any of the `values` (`$in`)
+    pub fn contained_in<T: Into<Value>>(
+        self,
+        key: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let values: Vec<Value> = values.into_iter().map(Into::into).collect();
+        self.add_constraint(key, "$in", Value::Array(values))
+    }
+
+    /// Matches objects where `key` is less than or equal to `value` (`$lte`)
+    pub fn less_than_or_equal_to(self, key: &str, value: impl Into<Value>) -> Self {
+        self.add_constraint(key, "$lte", value.into())
+    }
+
+    /// Matches objects where the date `key` is in `[start, end)` (`$gte` and `$lt`)
+    pub fn date_range(self, key: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.add_constraint(key, "$gte", date_value(start))
+            .add_constraint(key, "$lt", date_value(end))
+    }
+
+    /// Matches objects that match any of the given queries (`$or`)
+    ///
+    /// Only the constraints of the given queries are used
+    pub fn or(mut self, queries: Vec<ParseQuery>) -> Self {
+        let queries: Vec<Value> = queries
+            .into_iter()
+            .map(|q| Value::Object(q.constraints))
+            .collect();
+
+        self.constraints
+            .insert(String::from("$or"), Value::Array(queries));
+        self
+    }
+
+    /// Orders the results by `key` ascending, can be called multiple times
+    pub fn order(mut self, key: &str) -> Self {
+        self.order.push(key.to_owned());
+        self
+    }
+
+    /// Orders the results by `key` descending, can be called multiple times
+    pub fn order_desc(mut self, key: &str) -> Self {
+        self.order.push(format!("-{}", key));
+        self
+    }
+
+    /// Limits how many results are returned
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skips the first `skip` results
+    pub fn skip(mut self, skip: u32) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// Only returns the given keys of every object
+    pub fn keys(mut self, keys: &[&str]) -> Self {
+        self.keys.extend(keys.iter().map(|k| k.to_string()));
+        self
+    }
+
+    /// Also returns the total count of matching objects
+    pub fn count(mut self) -> Self {
+        self.count = true;
+        self
+    }
+
+    /// Returns the limit, if set
+    pub fn get_limit(&self) -> Option<u32> {
+        self.limit
+    }
+
+    /// Returns the skip, if set
+    pub fn get_skip(&self) -> Option<u32> {
+        self.skip
+    }
+
+    /// Returns the `where` constraints as a JSON string
+    pub fn get_where(&self) -> String {
+        Value::Object(self.constraints.clone()).to_string()
+    }
+
+    /// Returns every query parameter, ready for `Url::parse_with_params`
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params: Vec<(&'static str, String)> = vec![];
+
+        if !self.constraints.is_empty() {
+            params.push(("where", self.get_where()));
+        }
+        if !self.order.is_empty() {
+            params.push(("order", self.order.join(",")));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(skip) = self.skip {
+            params.push(("skip", skip.to_string()));
+        }
+        if !self.keys.is_empty() {
+            params.push(("keys", self.keys.join(",")));
+        }
+        if self.count {
+            params.push(("count", String::from("1")));
+        }
+
+        params
+    }
+
+    /// Adds an operator constraint to `key`, keeping any other operators on it
+    fn add_constraint(mut self, key: &str, operator: &str, value: Value) -> Self {
+        let entry = self
+            .constraints
+            .entry(key.to_owned())
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+
+        entry
+            .as_object_mut()
+            .unwrap()
+            .insert(operator.to_owned(), value);
+
+        self
+    }
+}
+
+/// Parse's JSON representation of a date
+fn date_value(date: DateTime<Utc>) -> Value {
+    json!({
+        "__type": "Date",
+        "iso": date.to_rfc3339_opts(SecondsFormat::Millis, true)
+    })
+}
+
+#[test]
+fn test_query_params() {
+    let query = ParseQuery::new()
+        .equal_to("mapID", "SP_1")
+        .order("time")
+        .order_desc("updatedAt")
+        .limit(10)
+        .skip(20)
+        .keys(&["time", "username"])
+        .count();
+
+    assert_eq!(
+        vec![
+            ("where", String::from(r#"{"mapID":"SP_1"}"#)),
+            ("order", String::from("time,-updatedAt")),
+            ("limit", String::from("10")),
+            ("skip", String::from("20")),
+            ("keys", String::from("time,username")),
+            ("count", String::from("1")),
+        ],
+        query.to_params()
+    );
+    assert!(ParseQuery::new().to_params().is_empty());
+}
+
+#[test]
+fn test_query_constraints() {
+    let start = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    let end = DateTime::parse_from_rfc3339("2024-01-08T00:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+
+    let query = ParseQuery::new()
+        .contained_in("mapID", ["SP_1", "SP_2"])
+        .date_range("updatedAt", start, end)
+        .or(vec![ParseQuery::new()
+            .equal_to("mapID", "SP_1")
+            .less_than_or_equal_to("time", 12.5)]);
+
+    let value: Value = serde_json::from_str(&query.get_where()).unwrap();
+    assert_eq!(
+        json!({
+            "mapID": { "$in": ["SP_1", "SP_2"] },
+            "updatedAt": {
+                "$gte": { "__type": "Date", "iso": "2024-01-01T00:00:00.000Z" },
+                "$lt": { "__type": "Date", "iso": "2024-01-08T00:00:00.000Z" }
+            },
+            "$or": [{ "mapID": "SP_1", "time": { "$lte": 12.5 } }]
+        }),
+        value
+    );
+}
@@ -0,0 +1,139 @@
+//! A shared scheduler that every Parse request goes through
+//!
+//! Limits how many requests are in flight at once and how many are sent per minute,
+//! configured by `parse.limits` in the settings
+
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::sleep,
+};
+
+use crate::config::{ParseLimits, SETTINGS};
+
+lazy_static! {
+    /// Global scheduler used by `request::raw_request`, use `acquire` instead
+    ///
+    /// Replaced by `rebuild` when `parse.limits` changes
+    pub static ref SCHEDULER: RwLock<Arc<Scheduler>> = {
+        let limits = SETTINGS.read().unwrap().parse.limits.clone();
+        RwLock::new(Arc::new(Scheduler::from_limits(&limits)))
+    };
+}
+
+/// Waits until the global scheduler allows a request to be sent
+pub async fn acquire() -> OwnedSemaphorePermit {
+    let scheduler = SCHEDULER.read().unwrap().clone();
+    scheduler.acquire().await
+}
+
+/// Replaces the global scheduler with one using the new limits
+///
+/// Requests already waiting or in flight finish on the old scheduler
+pub fn rebuild(limits: &ParseLimits) {
+    *SCHEDULER.write().unwrap() = Arc::new(Scheduler::from_limits(limits));
+}
+
+/// Hands out permits to send requests, within a concurrency and rate budget
+pub struct Scheduler {
+    permits: Arc<Semaphore>,
+    /// The minimum time between two requests, `None` if unlimited
+    interval: Option<Duration>,
+    /// When the next request is allowed to be sent
+    next_slot: Mutex<Instant>,
+}
+
+impl Scheduler {
+    /// Creates a new scheduler
+    ///
+    /// `requests_per_minute` of 0 means no rate limit, concurrency is always at least 1
+    pub fn new(max_concurrency: usize, requests_per_minute: u32) -> Self {
+        let interval = match requests_per_minute {
+            0 => None,
+            rpm => Some(Duration::from_secs(60) / rpm),
+        };
+
+        Scheduler {
+            permits: Arc::new(Semaphore::new(max_concurrency.max(1))),
+            interval,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Creates a new scheduler from `parse.limits`
+    pub fn from_limits(limits: &ParseLimits) -> Self {
+        Scheduler::new(limits.max_concurrency, limits.requests_per_minute)
+    }
+
+    /// Waits until a request is allowed to be sent
+    ///
+    /// The request should be sent while the returned permit is held
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Scheduler semaphore is never closed");
+
+        if let Some(interval) = self.interval {
+            let wait = {
+                let mut next_slot = self.next_slot.lock().unwrap();
+                let now = Instant::now();
+                let slot = (*next_slot).max(now);
+
+                *next_slot = slot + interval;
+                slot - now
+            };
+
+            sleep(wait).await;
+        }
+
+        permit
+    }
+}
+
+#[tokio::test]
+async fn test_scheduler_rate() {
+    // 10ms between every request
+    let scheduler = Scheduler::new(4, 6000);
+
+    let start = Instant::now();
+    for _ in 0..5 {
+        drop(scheduler.acquire().await);
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(40));
+}
+
+#[tokio::test]
+async fn test_scheduler_concurrency() {
+    let scheduler = Scheduler::new(2, 0);
+
+    let first = scheduler.acquire().await;
+    let _second = scheduler.acquire().await;
+    assert_eq!(0, scheduler.permits.available_permits());
+
+    drop(first);
+    assert_eq!(1, scheduler.permits.available_permits());
+}
+
+#[tokio::test]
+async fn test_rebuild() {
+    crate::test_util::init_test_settings();
+
+    rebuild(&ParseLimits {
+        max_concurrency: 3,
+        requests_per_minute: 0,
+        batch_size: 0,
+    });
+
+    let permit = acquire().await;
+    assert_eq!(2, SCHEDULER.read().unwrap().permits.available_permits());
+    drop(permit);
+}
@@ -2,13 +2,78 @@
 
 use chrono::Utc;
 use rand::{rngs::ThreadRng, seq::SliceRandom, Rng};
-use std::ops::Range;
+use std::{fs, ops::Range, sync::Once};
 
-use crate::miu::score::{Replay, Score};
+use crate::{
+    config::{self, ConfigSources},
+    miu::score::{Replay, Score},
+};
+
+/// The settings every test runs with, nothing is sent anywhere
+///
+/// `{fixture_dir}` is replaced with the directory written by `init_test_settings`
+const TEST_CONFIG: &str = r#"
+database_url = "sqlite::memory:"
+loop_wait_seconds = 1
+fixture_dir = "{fixture_dir}"
+
+[discord]
+webhooks = []
+weekly_webhooks = []
+
+[parse]
+domain = "localhost"
+appid = "appid"
+class_name = "class"
+
+[parse.weekly]
+class_name = "challenge"
+class_name_stats = "challenge_stats"
+
+[discovery]
+interval_seconds = 3600
+announce = true
+"#;
+
+/// The fixture world records every test backend loaded from settings starts with
+const TEST_WRS: &str = r#"[{
+    "time": 5.5, "userID": "UserId1", "username": "Username1", "mapID": "SP_bunny_slope",
+    "skinUsed": "swirl", "replayVersion": 5, "platform": "PC",
+    "createdAt": "2024-01-01T00:00:00Z", "updatedAt": "2024-01-01T00:00:00Z"
+}]"#;
+
+static INIT_SETTINGS: Once = Once::new();
+
+/// Points `config::SETTINGS` at a test config file, backed by a fixture directory
+///
+/// Must be called before anything reads `config::SETTINGS`
+pub fn init_test_settings() {
+    INIT_SETTINGS.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("miuu_test_{}", std::process::id()));
+        let fixture_dir = dir.join("fixtures");
+        fs::create_dir_all(&fixture_dir).unwrap();
+        fs::write(fixture_dir.join("wrs.json"), TEST_WRS).unwrap();
+
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            TEST_CONFIG.replace(
+                "{fixture_dir}",
+                &fixture_dir.to_string_lossy().replace('\\', "/"),
+            ),
+        )
+        .unwrap();
+
+        config::set_sources(ConfigSources {
+            path: Some(path.to_string_lossy().to_string()),
+            overrides: vec![],
+        });
+    });
+}
 
 /// Generates a fake score
 pub fn get_fake_score(time_range: Range<f32>) -> Score {
-    fn get_random_elem(mut rng: &mut ThreadRng, vec: &Vec<String>) -> String {
+    fn get_random_elem(mut rng: &mut ThreadRng, vec: &[String]) -> String {
         vec.choose(&mut rng).unwrap().to_owned()
     }
 
@@ -33,5 +98,6 @@ pub fn get_fake_score(time_range: Range<f32>) -> Score {
         created_at: Utc::now(),
         updated_at: Utc::now(),
         object_id: None,
+        record_id: None,
     }
 }